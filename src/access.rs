@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use warp::http::{Method, Uri};
+use warp::{Filter, Rejection};
+
+/// Rejection for browser requests from a web origin that may not read or delete payloads
+#[derive(Debug)]
+pub struct ForbiddenOrigin(pub String);
+
+impl warp::reject::Reject for ForbiddenOrigin {}
+
+/// Reduces an allowed origin from the settings to `scheme://host[:port]`, or `None`
+/// when it is not one, such as a bare host name or a URL with credentials
+fn normalize(origin: &str) -> Option<String> {
+    let uri = origin.trim().parse::<Uri>().ok()?;
+    let scheme = uri.scheme_str().filter(|scheme| matches!(*scheme, "http" | "https"))?;
+    let authority = uri.authority().filter(|authority| !authority.as_str().contains('@'))?;
+    if !matches!(uri.path(), "" | "/") || uri.query().is_some() {
+        return None;
+    }
+    Some(format!("{scheme}://{}", authority.as_str().to_ascii_lowercase()))
+}
+
+/// Web origins allowed to use the query API and live stream, read from the settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AllowedOrigins(Vec<String>);
+
+impl AllowedOrigins {
+    pub fn new(origins: &[String]) -> Self {
+        Self(
+            origins
+                .iter()
+                .filter_map(|origin| {
+                    let normalized = normalize(origin);
+                    if normalized.is_none() {
+                        eprintln!("WARN: Ignoring allowed origin '{origin}', which is not like http://host:port");
+                    }
+                    normalized
+                })
+                .collect(),
+        )
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.0.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin))
+    }
+
+    /// Refuses browser requests from pages on origins that are not allowed, so a site
+    /// the user visits cannot read, stream or delete their payloads. Requests without
+    /// an `Origin` header come from the app, scripts or tools such as curl, not a web page.
+    /// Apply it to every route that exposes stored payloads, after the method and path filters.
+    pub fn gate(&self) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+        let allowed = Arc::new(self.clone());
+        warp::header::optional::<String>("origin")
+            .and_then(move |origin: Option<String>| {
+                let allowed = Arc::clone(&allowed);
+                async move {
+                    match origin {
+                        Some(origin) if !allowed.allows(&origin) => {
+                            Err(warp::reject::custom(ForbiddenOrigin(origin)))
+                        }
+                        _ => Ok::<_, Rejection>(()),
+                    }
+                }
+            })
+            .untuple_one()
+    }
+
+    /// CORS for the routes behind `gate`, answering only the allowed origins
+    pub fn cors(&self) -> warp::cors::Builder {
+        warp::cors()
+            .allow_origins(self.0.iter().map(String::as_str))
+            .allow_methods(&[Method::GET, Method::DELETE, Method::OPTIONS])
+            .allow_headers(vec!["Content-Type", "Authorization", "Accept", "Origin", "X-Requested-With"])
+            .max_age(3600)
+    }
+}
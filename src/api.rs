@@ -0,0 +1,198 @@
+use crate::access::AllowedOrigins;
+use crate::payload::Payload;
+use crate::search::{self, SearchQuery};
+use crate::server::ServerMessage;
+use crate::storage::Storage;
+use chrono::{DateTime, Utc};
+use futures::channel::mpsc::Sender;
+use futures::SinkExt;
+use serde::Deserialize;
+use serde_json::json;
+use std::convert::Infallible;
+use warp::http::StatusCode;
+use warp::reply::{Reply, Response};
+use warp::Filter;
+
+/// Filters of `GET /payloads`, all optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ListQuery {
+    channel: Option<String>,
+    /// Receive time as RFC 3339 or Unix milliseconds; older payloads are left out
+    since: Option<String>,
+    /// Most payloads returned, newest first
+    limit: Option<String>,
+    /// Filter expression, as typed in the search bar
+    search: Option<String>,
+    /// Set to include the stored request headers, which may hold credentials
+    headers: Option<String>,
+}
+
+/// Options of `GET /payloads/{id}`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GetQuery {
+    headers: Option<String>,
+}
+
+/// Channel of `DELETE /payloads`; without one every channel is cleared
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DeleteQuery {
+    channel: Option<String>,
+}
+
+/// A JSON error body such as `{"error": "..."}` with the given status
+pub fn error_reply(status: StatusCode, message: impl Into<String>) -> Response {
+    warp::reply::with_status(warp::reply::json(&json!({ "error": message.into() })), status).into_response()
+}
+
+fn storage_error(e: &std::io::Error) -> Response {
+    eprintln!("ERROR: Payload API storage failure: {e}");
+    error_reply(StatusCode::INTERNAL_SERVER_ERROR, format!("Storage failure: {e}"))
+}
+
+/// Whether `?headers`, `?headers=1` or `?headers=true` asked for request headers
+pub(crate) fn wants_headers(headers: Option<&str>) -> bool {
+    matches!(headers.map(str::trim), Some("" | "1" | "true"))
+}
+
+/// Leaves the stored request headers, such as `Authorization` and `Cookie`, out of a
+/// payload record unless the caller asked for them
+pub(crate) fn redact(mut payload: Payload, headers: bool) -> Payload {
+    if !headers {
+        if let Some(request) = &mut payload.request {
+            request.headers.clear();
+        }
+    }
+    payload
+}
+
+fn parse_since(since: &str) -> Result<DateTime<Utc>, String> {
+    let since = since.trim();
+    since
+        .parse::<i64>()
+        .ok()
+        .and_then(DateTime::<Utc>::from_timestamp_millis)
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(since)
+                .ok()
+                .map(|since| since.with_timezone(&Utc))
+        })
+        .ok_or_else(|| format!("'since' must be an RFC 3339 time or Unix milliseconds, not '{since}'"))
+}
+
+/// Stored payloads matching the query, newest first
+fn list(storage: &Storage, query: &ListQuery) -> Result<Vec<Payload>, String> {
+    let search = match query.search.as_deref() {
+        Some(search) => SearchQuery::parse(search).map_err(|e| format!("Invalid search: {e}"))?,
+        None => None,
+    };
+    let since = query.since.as_deref().map(parse_since).transpose()?;
+    let limit = match query.limit.as_deref() {
+        Some(limit) => limit
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("'limit' must be a whole number, not '{limit}'"))?,
+        None => usize::MAX,
+    };
+
+    let headers = wants_headers(query.headers.as_deref());
+
+    let payloads = storage.get_all();
+    Ok(search::filter_payloads(&payloads, query.channel.as_deref(), search.as_ref())
+        .into_iter()
+        .filter(|payload| since.is_none_or(|since| payload.received_at >= since))
+        .take(limit)
+        .map(|payload| redact(payload.clone(), headers))
+        .collect())
+}
+
+/// Lets the app reload its list after payloads were deleted through the API
+async fn notify_deleted(mut output: Sender<ServerMessage>) {
+    let _ = output.send(ServerMessage::PayloadsDeleted).await;
+}
+
+/// Read and delete endpoints over the stored payloads:
+/// `GET /payloads`, `GET /payloads/{id}`, `DELETE /payloads/{id}` and `DELETE /payloads`.
+/// Web pages may only use them from `origins`.
+pub fn routes(
+    storage: Storage,
+    output: Sender<ServerMessage>,
+    origins: &AllowedOrigins,
+) -> impl Filter<Extract = (Response,), Error = warp::Rejection> + Clone {
+    let with_storage = warp::any().map(move || storage.clone());
+    let with_output = warp::any().map(move || output.clone());
+
+    let list_payloads = warp::get()
+        .and(warp::path!("payloads"))
+        .and(origins.gate())
+        .and(warp::query::<ListQuery>().or(warp::any().map(ListQuery::default)).unify())
+        .and(with_storage.clone())
+        .map(|query: ListQuery, storage: Storage| match list(&storage, &query) {
+            Ok(payloads) => warp::reply::json(&payloads).into_response(),
+            Err(e) => error_reply(StatusCode::BAD_REQUEST, e),
+        });
+
+    let get_payload = warp::get()
+        .and(warp::path!("payloads" / String))
+        .and(origins.gate())
+        .and(warp::query::<GetQuery>().or(warp::any().map(GetQuery::default)).unify())
+        .and(with_storage.clone())
+        .map(|id: String, query: GetQuery, storage: Storage| {
+            match storage.get_all().into_iter().find(|payload| payload.id == id) {
+                Some(payload) => {
+                    let payload = redact(payload, wants_headers(query.headers.as_deref()));
+                    warp::reply::json(&payload).into_response()
+                }
+                None => error_reply(StatusCode::NOT_FOUND, format!("No payload with id '{id}'")),
+            }
+        });
+
+    let delete_payload = warp::delete()
+        .and(warp::path!("payloads" / String))
+        .and(origins.gate())
+        .and(with_storage.clone())
+        .and(with_output.clone())
+        .and_then(|id: String, storage: Storage, output| async move {
+            let reply = match storage.delete(&id) {
+                Ok(true) => {
+                    notify_deleted(output).await;
+                    StatusCode::NO_CONTENT.into_response()
+                }
+                Ok(false) => error_reply(StatusCode::NOT_FOUND, format!("No payload with id '{id}'")),
+                Err(e) => storage_error(&e),
+            };
+            Ok::<_, Infallible>(reply)
+        });
+
+    // Pinned payloads survive, as they do the clear button
+    let delete_payloads = warp::delete()
+        .and(warp::path!("payloads"))
+        .and(origins.gate())
+        .and(warp::query::<DeleteQuery>().or(warp::any().map(DeleteQuery::default)).unify())
+        .and(with_storage)
+        .and(with_output)
+        .and_then(|query: DeleteQuery, storage: Storage, output| async move {
+            let result = match &query.channel {
+                Some(channel) => storage.delete_channel(channel, true).map(|_| ()),
+                None => storage.delete_all(true),
+            };
+            let reply = match result {
+                Ok(()) => {
+                    notify_deleted(output).await;
+                    StatusCode::NO_CONTENT.into_response()
+                }
+                Err(e) => storage_error(&e),
+            };
+            Ok::<_, Infallible>(reply)
+        });
+
+    list_payloads
+        .or(get_payload)
+        .unify()
+        .or(delete_payload)
+        .unify()
+        .or(delete_payloads)
+        .unify()
+}
@@ -1,37 +1,219 @@
-use crate::server::ServerMessage;
-use crate::settings::Settings;
-use crate::storage::Storage;
+use crate::access::AllowedOrigins;
+use crate::cli::Args;
+use crate::clipboard::CopyTarget;
+use crate::components::json_highlight::HighlightCache;
+use crate::diff::Diff;
+use crate::export::ExportFormat;
+use crate::payload::Payload;
+use crate::search::SearchQuery;
+use crate::server::{ListenAddress, ServerMessage, ServerStatus};
+use crate::settings::{Expansion, Retention, Settings};
+use crate::storage::{ImportSummary, Storage};
 use iced::event::Event;
-use serde_json::Value;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
 /// Application state and logic
 pub(crate) struct App {
     pub(crate) show_modal: bool,
     pub(crate) settings: Settings,
     pub(crate) storage: Storage,
-    pub(crate) expanded_payload_id: Option<String>,
-    pub(crate) collapsed_json_lines: HashSet<usize>,
-    pub(crate) payload_list_cache: Vec<(String, Value)>,
+    pub(crate) expanded_payload_ids: HashSet<String>,
+    /// The payload expanded because it was the newest, collapsed again when a newer one arrives
+    pub(crate) auto_expanded_id: Option<String>,
+    /// Nodes collapsed or expanded by hand, per payload id and JSON pointer,
+    /// overriding the remembered collapsed paths
+    pub(crate) collapsed_json_nodes: HashMap<String, HashMap<String, bool>>,
+    /// Remembered collapsed paths, parsed once whenever they change
+    pub(crate) remembered_paths: Vec<SearchQuery>,
+    /// Collapsed nodes of each expanded payload, computed in `update` when the payload
+    /// expands or what is collapsed changes, so rendering only reads them
+    pub(crate) collapsed_node_cache: HashMap<String, HashSet<String>>,
+    /// Payload id and JSON pointer of the node that copy shortcuts act on
+    pub(crate) selected_json_node: Option<(String, String)>,
+    /// Scroll offsets of the expanded payloads' JSON views, per payload id
+    pub(crate) json_scroll: HashMap<String, f32>,
+    pub(crate) highlight_cache: HighlightCache,
+    /// Set when the expanded payloads, the payload list or what is collapsed changed,
+    /// so `update` only refreshes the highlight and collapsed node caches then
+    pub(crate) view_caches_stale: bool,
+    pub(crate) payload_list_cache: Vec<Payload>,
+    pub(crate) channel_list_cache: Vec<(String, usize)>,
+    pub(crate) selected_channel: Option<String>,
+    pub(crate) search_input: String,
+    pub(crate) search_query: Option<SearchQuery>,
+    pub(crate) search_error: Option<String>,
+    /// Cached payloads matching `search_query`, by id, with the JSON pointers its path
+    /// selects in each. Kept up to date in `update` so rendering never runs the search.
+    pub(crate) search_matches: HashMap<String, HashSet<String>>,
+    pub(crate) args: Args,
+    pub(crate) listen_address: ListenAddress,
+    /// Web origins from the settings allowed to read payloads, passed to the server
+    pub(crate) allowed_origins: AllowedOrigins,
+    pub(crate) server_status: ServerStatus,
+    pub(crate) listen_host_input: String,
+    pub(crate) listen_port_input: String,
+    pub(crate) retention_inputs: RetentionInputs,
+    pub(crate) editor_command_input: String,
+    /// Why the last payload origin could not be opened, shown until dismissed
+    pub(crate) editor_error: Option<String>,
+    /// Payloads evicted by the retention policy since the notice was last dismissed
+    pub(crate) evicted_count: usize,
+    /// While set, clicking a payload picks it for comparison instead of expanding it
+    pub(crate) compare_mode: bool,
+    pub(crate) compare_pick: Option<String>,
+    /// Old and new payload ids of the diff being shown
+    pub(crate) diff_ids: Option<(String, String)>,
+    /// The diff of `diff_ids`, computed once when they are set rather than on every render
+    pub(crate) diff: Option<Diff>,
+    pub(crate) show_export: bool,
+    /// Outcome of the last export: the file written, or the error
+    pub(crate) export_status: Option<Result<String, String>>,
+    /// While paused, payloads are stored as they arrive but held back from the list
+    pub(crate) paused: bool,
+    /// Ids of payloads received while paused and not yet shown
+    pub(crate) held_payload_ids: HashSet<String>,
+    pub(crate) show_import: bool,
+    pub(crate) import_path_input: String,
+    /// Channel to import into; empty means a channel named after the file
+    pub(crate) import_channel_input: String,
+    /// Outcome of the last import: a summary, or the error
+    pub(crate) import_status: Option<Result<String, String>>,
 }
 
-impl Default for App {
-    fn default() -> Self {
-        let storage = Storage::new().expect("Failed to initialize storage");
+/// Text inputs for the global retention limits; an empty input means unlimited
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RetentionInputs {
+    /// Megabytes
+    pub(crate) size: String,
+    pub(crate) count: String,
+    /// Hours
+    pub(crate) age: String,
+}
+
+impl RetentionInputs {
+    pub(crate) fn new(retention: &Retention) -> Self {
+        Self {
+            size: retention
+                .max_bytes
+                .map(|bytes| (bytes as f64 / (1024.0 * 1024.0)).to_string())
+                .unwrap_or_default(),
+            count: retention.max_count.map(|count| count.to_string()).unwrap_or_default(),
+            age: retention
+                .max_age_secs
+                .map(|secs| (secs as f64 / 3600.0).to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Applies the inputs on top of `current`, keeping its per-channel limits
+    pub(crate) fn parse(&self, current: &Retention) -> Result<Retention, String> {
+        fn positive(input: &str, name: &str) -> Result<Option<f64>, String> {
+            let input = input.trim();
+            if input.is_empty() {
+                return Ok(None);
+            }
+            match input.parse::<f64>() {
+                Ok(value) if value.is_finite() && value > 0.0 => Ok(Some(value)),
+                _ => Err(format!("{name} must be a positive number, or empty for no limit")),
+            }
+        }
+
+        let max_bytes = positive(&self.size, "Size")?.map(|mb| (mb * 1024.0 * 1024.0) as u64);
+        let max_age_secs = positive(&self.age, "Age")?.map(|hours| (hours * 3600.0) as u64);
+        let max_count = match self.count.trim() {
+            "" => None,
+            count => Some(
+                count
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or("Count must be a whole number above zero, or empty for no limit")?,
+            ),
+        };
+
+        Ok(Retention {
+            max_bytes,
+            max_count,
+            max_age_secs,
+            channels: current.channels.clone(),
+        })
+    }
+}
+
+impl App {
+    pub(crate) fn new(args: Args, storage: Storage) -> Self {
+        let settings = Settings::load();
         let payload_list_cache = storage.get_all();
-        let newest_payload_id = payload_list_cache.first().map(|(id, _)| id.clone());
+        let channel_list_cache = storage.channels();
+        let auto_expanded_id = match settings.expansion() {
+            Expansion::ExpandNewest => payload_list_cache.first().map(|payload| payload.id.clone()),
+            _ => None,
+        };
+        let expanded_payload_ids = match settings.expansion() {
+            Expansion::ExpandNewest => auto_expanded_id.iter().cloned().collect(),
+            Expansion::ExpandAll => payload_list_cache.iter().map(|payload| payload.id.clone()).collect(),
+            Expansion::CollapseAll => HashSet::new(),
+        };
+        let listen_address = ListenAddress::resolve(&settings, &args);
+        let remembered_paths = parse_remembered_paths(&settings);
+        let allowed_origins = AllowedOrigins::new(settings.allowed_origins());
 
         Self {
             show_modal: false,
-            settings: Settings::load(),
+            listen_host_input: settings.listen_host().to_string(),
+            listen_port_input: settings.listen_port().to_string(),
+            retention_inputs: RetentionInputs::new(settings.retention()),
+            editor_command_input: settings.editor_command().to_string(),
+            editor_error: None,
+            evicted_count: 0,
+            compare_mode: false,
+            compare_pick: None,
+            diff_ids: None,
+            diff: None,
+            show_export: false,
+            export_status: None,
+            paused: false,
+            held_payload_ids: HashSet::new(),
+            show_import: false,
+            import_path_input: String::new(),
+            import_channel_input: String::new(),
+            import_status: None,
+            settings,
             storage,
-            expanded_payload_id: newest_payload_id,
-            collapsed_json_lines: HashSet::new(),
+            expanded_payload_ids,
+            auto_expanded_id,
+            collapsed_json_nodes: HashMap::new(),
+            remembered_paths,
+            collapsed_node_cache: HashMap::new(),
+            selected_json_node: None,
+            json_scroll: HashMap::new(),
+            highlight_cache: HighlightCache::default(),
+            view_caches_stale: true,
             payload_list_cache,
+            channel_list_cache,
+            selected_channel: None,
+            search_input: String::new(),
+            search_query: None,
+            search_error: None,
+            search_matches: HashMap::new(),
+            args,
+            listen_address,
+            allowed_origins,
+            server_status: ServerStatus::default(),
         }
     }
 }
 
+/// Parses the remembered collapsed paths, skipping any that no longer parse
+pub(crate) fn parse_remembered_paths(settings: &Settings) -> Vec<SearchQuery> {
+    settings
+        .collapsed_paths()
+        .iter()
+        .filter_map(|path| SearchQuery::parse(path).ok().flatten())
+        .collect()
+}
+
 /// Messages used for application state updates
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -42,10 +224,53 @@ pub(crate) enum Message {
     Server(ServerMessage),
     ThemeChanged(usize),
     TogglePayload(String),
-    ToggleJsonSection(usize),
+    ToggleJsonSection(String, String),
+    SelectJsonNode(String, String),
+    JsonScrolled(String, f32),
+    Copy(String, CopyTarget),
     ClearPayloads,
+    ClearChannel(String),
+    SelectChannel(Option<String>),
+    SearchChanged(String),
     DeletePayload(String),
+    TogglePin(String),
+    /// Changes to the stored payloads reached the disk, or failed to
+    PayloadsSaved(Result<(), String>),
+    ToggleCompareMode,
+    DiffWithPrevious(String),
+    CloseDiff,
+    ToggleExport,
+    Export(ExportFormat),
+    /// An export finished: the file written, or the error
+    Exported(Result<String, String>),
+    ToggleImport,
+    ImportPathChanged(String),
+    ImportChannelChanged(String),
+    Import,
+    /// An import finished, into the named channel
+    Imported(String, Result<ImportSummary, String>),
+    FileDropped(PathBuf),
     WindowMoved(iced::Point),
     WindowResized(iced::Size),
     WindowClosed,
+    ListenHostChanged(String),
+    ListenPortChanged(String),
+    ApplyListenAddress,
+    RetentionSizeChanged(String),
+    RetentionCountChanged(String),
+    RetentionAgeChanged(String),
+    ApplyRetention,
+    TogglePause,
+    ShowHeldPayloads,
+    ExpandAll,
+    CollapseAll,
+    ExpansionChanged(Expansion),
+    FollowTailToggled(bool),
+    EditorCommandChanged(String),
+    ApplyEditorCommand,
+    OpenOrigin(String),
+    DismissEditorError,
+    RememberCollapsedPathsToggled(bool),
+    ForgetCollapsedPaths,
+    DismissEvictions,
 } 
\ No newline at end of file
@@ -0,0 +1,237 @@
+use crate::payload::{encode_hex, PayloadKind};
+use futures::TryStreamExt;
+use serde_json::{Map, Value};
+use warp::hyper::body::Buf;
+use warp::multipart::{FormData, Part};
+
+/// Returns the lowercase media type of a `Content-Type` header, without parameters
+fn media_type(content_type: Option<&str>) -> String {
+    content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Inserts a form field, turning repeated names into an array of values
+fn insert_field(fields: &mut Map<String, Value>, name: String, value: Value) {
+    match fields.get_mut(&name) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+        None => {
+            fields.insert(name, value);
+        }
+    }
+}
+
+/// Parses newline-delimited JSON, keeping lines that are not JSON as text payloads
+fn decode_ndjson(text: &str) -> Vec<(PayloadKind, Value)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match serde_json::from_str::<Value>(line) {
+            Ok(value) => (PayloadKind::Json, value),
+            Err(_) => (PayloadKind::Text, Value::String(line.to_string())),
+        })
+        .collect()
+}
+
+fn decode_form(bytes: &[u8]) -> Value {
+    let mut fields = Map::new();
+    for (name, value) in form_urlencoded::parse(bytes) {
+        insert_field(
+            &mut fields,
+            name.into_owned(),
+            Value::String(value.into_owned()),
+        );
+    }
+    Value::Object(fields)
+}
+
+/// Decodes a raw request body into one or more payloads based on its content type.
+/// Bodies that cannot be interpreted as declared fall back to text, then binary,
+/// so nothing the sender posts is dropped.
+pub fn decode(content_type: Option<&str>, bytes: &[u8]) -> Vec<(PayloadKind, Value)> {
+    let media_type = media_type(content_type);
+
+    if media_type == "application/x-www-form-urlencoded" {
+        return vec![(PayloadKind::Form, decode_form(bytes))];
+    }
+
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return vec![(PayloadKind::Binary, Value::String(encode_hex(bytes)))];
+    };
+
+    match media_type.as_str() {
+        "application/x-ndjson"
+        | "application/ndjson"
+        | "application/jsonl"
+        | "application/x-jsonlines" => decode_ndjson(text),
+        _ if media_type.starts_with("text/") => {
+            vec![(PayloadKind::Text, Value::String(text.to_string()))]
+        }
+        _ => {
+            if let Ok(value) = serde_json::from_str::<Value>(text) {
+                return vec![(PayloadKind::Json, value)];
+            }
+
+            let lines = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>();
+
+            // Several JSON documents on separate lines, sent without an NDJSON content type
+            if lines.len() > 1
+                && lines
+                    .iter()
+                    .all(|line| serde_json::from_str::<Value>(line).is_ok())
+            {
+                decode_ndjson(text)
+            } else if lines.is_empty() {
+                Vec::new()
+            } else {
+                vec![(PayloadKind::Text, Value::String(text.to_string()))]
+            }
+        }
+    }
+}
+
+/// Why a body declared as JSON failed to parse, or `None` when it parsed or was not declared as JSON
+pub fn json_error(content_type: Option<&str>, bytes: &[u8]) -> Option<String> {
+    let media_type = media_type(content_type);
+    if media_type != "application/json" && !media_type.ends_with("+json") {
+        return None;
+    }
+    serde_json::from_slice::<Value>(bytes).err().map(|e| e.to_string())
+}
+
+async fn read_part(part: Part) -> Result<Vec<u8>, warp::Error> {
+    part.stream()
+        .try_fold(Vec::new(), |mut data, mut chunk| async move {
+            data.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+            Ok(data)
+        })
+        .await
+}
+
+/// Reads a multipart form into an object of field names to values. Text fields
+/// become strings; file uploads become an object describing the file.
+pub async fn decode_multipart(form: FormData) -> Result<Value, warp::Error> {
+    let parts: Vec<Part> = form.try_collect().await?;
+    let mut fields = Map::new();
+
+    for part in parts {
+        let name = part.name().to_string();
+        let filename = part.filename().map(str::to_string);
+        let content_type = part.content_type().map(str::to_string);
+        let data = read_part(part).await?;
+
+        let value = match (filename, String::from_utf8(data)) {
+            (None, Ok(text)) => Value::String(text),
+            (filename, data) => {
+                let mut file = Map::new();
+                file.insert("filename".to_string(), filename.into());
+                file.insert("content_type".to_string(), content_type.into());
+                match data {
+                    Ok(text) => {
+                        file.insert("size".to_string(), text.len().into());
+                        file.insert("content".to_string(), Value::String(text));
+                    }
+                    Err(e) => {
+                        let bytes = e.into_bytes();
+                        file.insert("size".to_string(), bytes.len().into());
+                        file.insert("hex".to_string(), Value::String(encode_hex(&bytes)));
+                    }
+                }
+                Value::Object(file)
+            }
+        };
+
+        insert_field(&mut fields, name, value);
+    }
+
+    Ok(Value::Object(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_body_is_one_payload() {
+        assert_eq!(
+            decode(Some("application/json; charset=utf-8"), br#"{"a": 1}"#),
+            [(PayloadKind::Json, json!({ "a": 1 }))]
+        );
+        // Without a content type, JSON is still recognized
+        assert_eq!(decode(None, b"[1, 2]"), [(PayloadKind::Json, json!([1, 2]))]);
+    }
+
+    #[test]
+    fn ndjson_body_is_one_payload_per_line() {
+        assert_eq!(
+            decode(Some("application/x-ndjson"), b"{\"a\": 1}\n\nnot json\n[2]\n"),
+            [
+                (PayloadKind::Json, json!({ "a": 1 })),
+                (PayloadKind::Text, json!("not json")),
+                (PayloadKind::Json, json!([2])),
+            ]
+        );
+        // Several JSON lines sent without a content type are split as well
+        assert_eq!(
+            decode(None, b"{\"a\": 1}\r\n{\"b\": 2}"),
+            [
+                (PayloadKind::Json, json!({ "a": 1 })),
+                (PayloadKind::Json, json!({ "b": 2 })),
+            ]
+        );
+    }
+
+    #[test]
+    fn urlencoded_form_repeats_become_arrays() {
+        assert_eq!(
+            decode(
+                Some("application/x-www-form-urlencoded"),
+                b"name=dbug&tag=a&tag=b&note=hello+world%21"
+            ),
+            [(
+                PayloadKind::Form,
+                json!({ "name": "dbug", "tag": ["a", "b"], "note": "hello world!" })
+            )]
+        );
+    }
+
+    #[test]
+    fn text_body_is_kept_verbatim() {
+        assert_eq!(
+            decode(Some("text/plain"), b"{\"a\": 1}"),
+            [(PayloadKind::Text, json!("{\"a\": 1}"))]
+        );
+        assert_eq!(
+            decode(Some("application/json"), b"{not json"),
+            [(PayloadKind::Text, json!("{not json"))]
+        );
+        assert!(json_error(Some("application/json"), b"{not json").is_some());
+        assert!(json_error(Some("text/plain"), b"{not json").is_none());
+    }
+
+    #[test]
+    fn non_utf8_body_is_binary() {
+        assert_eq!(
+            decode(Some("application/octet-stream"), &[0xff, 0x00, 0x1a]),
+            [(PayloadKind::Binary, json!("ff001a"))]
+        );
+    }
+
+    #[test]
+    fn blank_body_holds_no_payload() {
+        assert!(decode(None, b"").is_empty());
+        assert!(decode(Some("application/json"), b" \n\t").is_empty());
+        assert!(decode(Some("application/x-ndjson"), b"\n\n").is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::export::ExportFormat;
+use std::fmt;
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: dbug-desktop [COMMAND] [OPTIONS]
+
+Commands:
+  serve              Run the payload server without the GUI, printing payloads to the terminal
+  export <FORMAT>    Write stored payloads as json, ndjson, har or markdown
+  import <FILE>      Store the payloads in a JSON, NDJSON or HAR file (while the app is closed)
+
+Options:
+  --host <HOST>      Address the payload server listens on (env: DBUG_HOST)
+  --port <PORT>      Port the payload server listens on (env: DBUG_PORT)
+  --format <FORMAT>  How `serve` prints payloads: pretty (default) or compact
+  --color <WHEN>     Whether `serve` colors its output: auto (default), always or never
+  --channel <NAME>   Only export payloads in this channel, or import into it (default: file name)
+  --search <QUERY>   Only export payloads matching this filter, as typed in the search bar
+  -o, --output <FILE>  File to export to instead of stdout
+  -h, --help         Print this help";
+
+/// Errors produced while parsing command line arguments
+#[derive(Debug)]
+pub struct ArgsError(String);
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{USAGE}", self.0)
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+/// What the process runs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Command {
+    #[default]
+    Gui,
+    /// Headless payload server printing to stdout
+    Serve,
+    /// Writes stored payloads to stdout or a file
+    Export(ExportFormat),
+    /// Stores the payloads from a file
+    Import(PathBuf),
+}
+
+/// How `serve` prints each payload
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A header line followed by the indented body
+    #[default]
+    Pretty,
+    /// One line per payload
+    Compact,
+}
+
+/// Whether `serve` writes ANSI colors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Command line options, layered on top of the persisted settings
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    pub command: Command,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub format: OutputFormat,
+    pub color: ColorChoice,
+    pub channel: Option<String>,
+    pub search: Option<String>,
+    pub output: Option<PathBuf>,
+}
+
+impl Args {
+    /// Parses the process arguments, printing usage and exiting on `--help`
+    pub fn parse() -> Result<Self, ArgsError> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self, ArgsError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // Accept both `--flag value` and `--flag=value`
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                "--host" => {
+                    parsed.host = Some(Self::value(&flag, inline_value, &mut args)?);
+                }
+                "--port" => {
+                    let value = Self::value(&flag, inline_value, &mut args)?;
+                    let port = value
+                        .parse::<u16>()
+                        .map_err(|_| ArgsError(format!("Invalid port '{value}'")))?;
+                    parsed.port = Some(port);
+                }
+                "--format" => {
+                    parsed.format = match Self::value(&flag, inline_value, &mut args)?.as_str() {
+                        "pretty" => OutputFormat::Pretty,
+                        "compact" => OutputFormat::Compact,
+                        other => return Err(ArgsError(format!("Invalid format '{other}'"))),
+                    };
+                }
+                "--color" => {
+                    parsed.color = match Self::value(&flag, inline_value, &mut args)?.as_str() {
+                        "auto" => ColorChoice::Auto,
+                        "always" => ColorChoice::Always,
+                        "never" => ColorChoice::Never,
+                        other => return Err(ArgsError(format!("Invalid color choice '{other}'"))),
+                    };
+                }
+                "--channel" => {
+                    parsed.channel = Some(Self::value(&flag, inline_value, &mut args)?);
+                }
+                "--search" => {
+                    parsed.search = Some(Self::value(&flag, inline_value, &mut args)?);
+                }
+                "-o" | "--output" => {
+                    parsed.output = Some(PathBuf::from(Self::value(&flag, inline_value, &mut args)?));
+                }
+                "serve" if parsed.command == Command::Gui => parsed.command = Command::Serve,
+                "export" if parsed.command == Command::Gui => {
+                    let name = args
+                        .next()
+                        .ok_or_else(|| ArgsError("Missing format for 'export'".to_string()))?;
+                    let format = ExportFormat::parse(&name)
+                        .ok_or_else(|| ArgsError(format!("Invalid export format '{name}'")))?;
+                    parsed.command = Command::Export(format);
+                }
+                "import" if parsed.command == Command::Gui => {
+                    let file = args
+                        .next()
+                        .ok_or_else(|| ArgsError("Missing file for 'import'".to_string()))?;
+                    parsed.command = Command::Import(PathBuf::from(file));
+                }
+                _ => return Err(ArgsError(format!("Unknown argument '{flag}'"))),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn value(
+        flag: &str,
+        inline_value: Option<String>,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<String, ArgsError> {
+        inline_value
+            .or_else(|| args.next())
+            .ok_or_else(|| ArgsError(format!("Missing value for '{flag}'")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, ArgsError> {
+        Args::parse_from(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn no_arguments_run_the_gui() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.command, Command::Gui);
+        assert_eq!(args.host, None);
+        assert_eq!(args.port, None);
+    }
+
+    #[test]
+    fn host_and_port_accept_separate_and_inline_values() {
+        let args = parse(&["--host", "0.0.0.0", "--port", "9000"]).unwrap();
+        assert_eq!(args.host.as_deref(), Some("0.0.0.0"));
+        assert_eq!(args.port, Some(9000));
+
+        let args = parse(&["serve", "--host=::1", "--port=8080"]).unwrap();
+        assert_eq!(args.command, Command::Serve);
+        assert_eq!(args.host.as_deref(), Some("::1"));
+        assert_eq!(args.port, Some(8080));
+    }
+
+    #[test]
+    fn invalid_or_missing_values_are_errors() {
+        assert!(parse(&["--port", "http"]).is_err());
+        assert!(parse(&["--port", "70000"]).is_err());
+        assert!(parse(&["--host"]).is_err());
+        assert!(parse(&["--format", "fancy"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
+
+    #[test]
+    fn commands_take_their_operands() {
+        let args = parse(&["import", "payloads.har", "--channel", "api"]).unwrap();
+        assert_eq!(args.command, Command::Import(PathBuf::from("payloads.har")));
+        assert_eq!(args.channel.as_deref(), Some("api"));
+
+        assert!(parse(&["export"]).is_err());
+        assert!(parse(&["export", "csv"]).is_err());
+        assert!(parse(&["serve", "serve"]).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use crate::payload::{Payload, PayloadKind};
+use crate::search::{format_path, path_from_pointer};
+use serde_json::Value;
+
+/// What a copy action puts on the clipboard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CopyTarget {
+    /// The whole payload, indented
+    Pretty,
+    /// The whole payload on one line
+    Compact,
+    /// The node at a JSON pointer: an object or array, or a single value
+    Value(String),
+    /// The JSONPath of the node at a JSON pointer
+    Path(String),
+}
+
+/// Text for a copied value: strings without quotes, everything else as JSON
+fn value_text(value: &Value, pretty: bool) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        value if pretty => {
+            serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+        }
+        value => value.to_string(),
+    }
+}
+
+/// The text `target` copies from `payload`, or `None` if the pointer addresses no node.
+/// Text and binary payloads copy their body as-is.
+pub fn copy_text(payload: &Payload, target: CopyTarget) -> Option<String> {
+    let structured = matches!(payload.kind, PayloadKind::Json | PayloadKind::Form);
+    match target {
+        CopyTarget::Pretty if structured => Some(
+            serde_json::to_string_pretty(&payload.value)
+                .unwrap_or_else(|_| payload.value.to_string()),
+        ),
+        CopyTarget::Compact if structured => Some(payload.value.to_string()),
+        CopyTarget::Pretty | CopyTarget::Compact => Some(value_text(&payload.value, false)),
+        CopyTarget::Value(pointer) => payload
+            .value
+            .pointer(&pointer)
+            .map(|value| value_text(value, true)),
+        CopyTarget::Path(pointer) => {
+            path_from_pointer(&payload.value, &pointer).map(|path| format_path(&path))
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::app::Message;
+use crate::components::styles;
+use crate::payload::decode_hex;
+use crate::search::SearchQuery;
+use iced::widget::{column, container, row, text};
+use iced::{Element, Fill, Theme};
+use serde_json::Value;
+
+// Bytes rendered per hex view line, and the most bytes a hex view will render
+const HEX_BYTES_PER_LINE: usize = 16;
+const HEX_MAX_BYTES: usize = 64 * 1024;
+
+fn line_number<'a>(idx: usize) -> Element<'a, Message> {
+    text(format!("{:>3} ", idx + 1))
+        .size(12)
+        .style(|theme: &Theme| text::Style {
+            color: Some(theme.extended_palette().background.strong.color),
+        })
+        .width(30)
+        .into()
+}
+
+/// Wraps a row in the search highlight when it matched
+fn highlight_row<'a>(
+    content: impl Into<Element<'a, Message>>,
+    highlighted: bool,
+) -> Element<'a, Message> {
+    if highlighted {
+        container(content).style(styles::container_highlight).into()
+    } else {
+        content.into()
+    }
+}
+
+/// Renders a plain text body with line numbers, highlighting lines that match the search
+pub fn text_view<'a>(body: &str, search: Option<&SearchQuery>) -> Element<'a, Message> {
+    column(
+        body.lines()
+            .enumerate()
+            .map(|(idx, line)| {
+                let highlighted = search.is_some_and(|query| query.highlights(line, false));
+                row![
+                    text(" ").width(15),
+                    line_number(idx),
+                    highlight_row(
+                        text(line.to_string()).style(|theme: &Theme| text::Style {
+                            color: Some(theme.extended_palette().primary.strong.color),
+                        }),
+                        highlighted,
+                    ),
+                ]
+                .into()
+            })
+            .collect::<Vec<_>>(),
+    )
+    .spacing(2)
+    .into()
+}
+
+/// Renders form fields as a two column table of names and values, highlighting matching fields
+pub fn form_view<'a>(fields: &Value, search: Option<&SearchQuery>) -> Element<'a, Message> {
+    let Value::Object(fields) = fields else {
+        return text(fields.to_string()).into();
+    };
+
+    let field_row = |name: &str, value: &Value| -> Element<'a, Message> {
+        let value = match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        let highlighted = search
+            .is_some_and(|query| query.highlights(name, true) || query.highlights(&value, false));
+        highlight_row(
+            row![
+                container(text(name.to_string()).style(|theme: &Theme| text::Style {
+                    color: Some(theme.extended_palette().secondary.base.text),
+                }))
+                .width(200),
+                text(value).width(Fill).style(|theme: &Theme| text::Style {
+                    color: Some(theme.extended_palette().primary.strong.color),
+                }),
+            ]
+            .spacing(10),
+            highlighted,
+        )
+    };
+
+    column(
+        fields
+            .iter()
+            .flat_map(|(name, value)| match value {
+                // Repeated fields are shown once per value
+                Value::Array(values) => values.iter().map(|value| field_row(name, value)).collect(),
+                value => vec![field_row(name, value)],
+            })
+            .collect::<Vec<_>>(),
+    )
+    .spacing(4)
+    .padding([0, 15])
+    .into()
+}
+
+/// Renders binary data as a classic offset / hex / ASCII dump
+pub fn hex_view<'a>(hex: &str) -> Element<'a, Message> {
+    let Some(bytes) = decode_hex(hex) else {
+        return text("Invalid binary payload").style(text::danger).into();
+    };
+
+    let shown = &bytes[..bytes.len().min(HEX_MAX_BYTES)];
+    let mut lines = shown
+        .chunks(HEX_BYTES_PER_LINE)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+
+            row![
+                text(format!("{:08x}  ", idx * HEX_BYTES_PER_LINE)).style(|theme: &Theme| {
+                    text::Style {
+                        color: Some(theme.extended_palette().background.strong.color),
+                    }
+                }),
+                text(format!(
+                    "{hex:<width$}  ",
+                    width = HEX_BYTES_PER_LINE * 3 - 1
+                ))
+                .style(|theme: &Theme| text::Style {
+                    color: Some(theme.extended_palette().success.weak.color),
+                }),
+                text(ascii).style(|theme: &Theme| text::Style {
+                    color: Some(theme.extended_palette().primary.weak.color),
+                }),
+            ]
+            .into()
+        })
+        .collect::<Vec<Element<'a, Message>>>();
+
+    if bytes.len() > shown.len() {
+        lines.push(
+            text(format!("... {} more bytes", bytes.len() - shown.len()))
+                .size(12)
+                .into(),
+        );
+    }
+
+    column(lines).spacing(2).padding([0, 15]).into()
+}
@@ -0,0 +1,38 @@
+use crate::app::Message;
+use iced::widget::{button, row, text};
+use iced::Element;
+
+fn channel_tab<'a>(label: String, selected: bool, on_press: Message) -> Element<'a, Message> {
+    button(text(label).size(12))
+        .style(if selected {
+            button::primary
+        } else {
+            button::secondary
+        })
+        .padding([3, 8])
+        .on_press(on_press)
+        .into()
+}
+
+/// Creates the header tabs for switching between channels, each showing its payload count
+pub fn channel_tabs<'a>(
+    channels: &[(String, usize)],
+    total: usize,
+    selected: Option<&String>,
+) -> Element<'a, Message> {
+    let all_tab = channel_tab(
+        format!("All {total}"),
+        selected.is_none(),
+        Message::SelectChannel(None),
+    );
+
+    row(std::iter::once(all_tab).chain(channels.iter().map(|(channel, count)| {
+        channel_tab(
+            format!("{channel} {count}"),
+            selected == Some(channel),
+            Message::SelectChannel(Some(channel.clone())),
+        )
+    })))
+    .spacing(5)
+    .into()
+}
@@ -0,0 +1,151 @@
+use crate::app::Message;
+use crate::components::json_highlight::color_for_token;
+use crate::components::styles;
+use crate::diff::{Change, Diff, DiffLine};
+use crate::payload::Payload;
+use iced::widget::{button, column, container, horizontal_space, row, scrollable, svg, text};
+use iced::{Color, Element, Fill, Theme};
+use serde_json::Value;
+
+/// Short label identifying a payload in the diff header
+fn payload_label(payload: &Payload) -> String {
+    format!(
+        "{} {}",
+        payload.channel,
+        payload.received_at.format("%H:%M:%S%.3f")
+    )
+}
+
+/// Colored text for a rendered scalar, unquoting strings the way the JSON view does
+fn value_text<'a>(rendered: &str, theme: &Theme) -> Element<'a, Message> {
+    let (token, in_string) = match serde_json::from_str::<Value>(rendered) {
+        Ok(Value::String(string)) => (string, true),
+        _ => (rendered.to_string(), false),
+    };
+    let color = color_for_token(&token, false, in_string, theme);
+    text(token)
+        .style(move |_| text::Style { color: Some(color) })
+        .into()
+}
+
+fn colored<'a>(content: &str, color: Color) -> Element<'a, Message> {
+    text(content.to_string())
+        .style(move |_| text::Style { color: Some(color) })
+        .into()
+}
+
+fn diff_line<'a>(line: &DiffLine, theme: &Theme) -> Element<'a, Message> {
+    let palette = theme.extended_palette();
+    let (marker, marker_color) = match line.change {
+        Change::Same => (" ", palette.background.strong.color),
+        Change::Added => ("+", palette.success.base.color),
+        Change::Removed => ("-", palette.danger.base.color),
+        Change::Changed(_) => ("~", palette.warning.base.color),
+        Change::Moved(_) => (">", palette.primary.base.color),
+    };
+
+    let mut tokens = vec![
+        colored(marker, marker_color),
+        text(" ".repeat(line.depth * 2 + 1)).into(),
+    ];
+    if let Some(key) = &line.key {
+        tokens.push(colored(key, color_for_token(key, true, true, theme)));
+        tokens.push(colored(": ", color_for_token(":", false, false, theme)));
+    }
+    match &line.change {
+        Change::Changed(old) => {
+            tokens.push(value_text(old, theme));
+            tokens.push(colored(" -> ", marker_color));
+            tokens.push(value_text(&line.text, theme));
+        }
+        Change::Moved(from) => {
+            tokens.push(value_text(&line.text, theme));
+            tokens.push(colored(&format!("  moved from [{from}]"), marker_color));
+        }
+        _ => tokens.push(value_text(&line.text, theme)),
+    }
+
+    container(row(tokens))
+        .width(Fill)
+        .style(styles::container_diff(&line.change))
+        .into()
+}
+
+/// Shows `diff`, the structural diff from `old` to `new`, with a summary and a close button
+pub fn diff_view<'a>(old: &Payload, new: &Payload, diff: &Diff, theme: &Theme) -> Element<'a, Message> {
+    let summary = if diff.is_empty() {
+        "No differences".to_string()
+    } else {
+        format!(
+            "{} added, {} removed, {} changed, {} moved",
+            diff.added, diff.removed, diff.changed, diff.moved
+        )
+    };
+
+    let close_svg = svg(svg::Handle::from_memory(
+        include_bytes!("../../assets/icons/mdi--close.svg").as_slice(),
+    ))
+    .width(Fill)
+    .height(Fill)
+    .style(styles::svg_style_secondary);
+
+    let header = row![
+        text(format!("{} -> {}", payload_label(old), payload_label(new))).size(14),
+        text(summary).size(12),
+        horizontal_space(),
+        button(close_svg)
+            .width(18)
+            .height(18)
+            .padding(0)
+            .on_press(Message::CloseDiff),
+    ]
+    .spacing(10)
+    .align_y(iced::alignment::Vertical::Center);
+
+    let lines = column(
+        diff.lines
+            .iter()
+            .map(|line| diff_line(line, theme))
+            .collect::<Vec<_>>(),
+    )
+    .spacing(2);
+
+    container(
+        column![
+            header,
+            scrollable(container(lines).padding(iced_core::Padding {
+                right: 10.0,
+                ..Default::default()
+            }))
+            .height(Fill),
+        ]
+        .spacing(10),
+    )
+    .padding(10)
+    .width(Fill)
+    .height(Fill)
+    .style(styles::container_code)
+    .into()
+}
+
+/// Prompt shown while picking the two payloads to compare
+pub fn compare_banner<'a>(picked: Option<&Payload>) -> Element<'a, Message> {
+    let prompt = match picked {
+        None => "Compare: select the first payload".to_string(),
+        Some(payload) => format!(
+            "Compare: select a payload to compare with {}",
+            payload_label(payload)
+        ),
+    };
+
+    row![
+        text(prompt).size(12).width(Fill),
+        button(text("Cancel").size(12))
+            .style(button::text)
+            .padding(0)
+            .on_press(Message::ToggleCompareMode),
+    ]
+    .padding([0, 10])
+    .align_y(iced::alignment::Vertical::Center)
+    .into()
+}
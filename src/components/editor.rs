@@ -0,0 +1,21 @@
+use crate::app::Message;
+use iced::widget::{button, column, row, text};
+use iced::{Element, Fill};
+
+/// Shows why the last payload origin could not be opened in the editor, nothing otherwise
+pub fn editor_error<'a>(error: Option<&str>) -> Element<'a, Message> {
+    let Some(error) = error else {
+        return column![].into();
+    };
+
+    row![
+        text(error.to_string()).size(12).style(text::danger).width(Fill),
+        button(text("Dismiss").size(12))
+            .style(button::text)
+            .padding(0)
+            .on_press(Message::DismissEditorError),
+    ]
+    .padding([0, 10])
+    .align_y(iced::alignment::Vertical::Center)
+    .into()
+}
@@ -0,0 +1,42 @@
+use crate::app::Message;
+use crate::export::ExportFormat;
+use iced::widget::{button, row, text};
+use iced::{Element, Fill};
+
+/// Buttons exporting the visible payloads, with the outcome of the last export
+pub fn export_bar<'a>(
+    count: usize,
+    status: Option<&Result<String, String>>,
+) -> Element<'a, Message> {
+    let mut items: Vec<Element<'a, Message>> =
+        vec![text(format!("Export {count} payloads as")).size(12).into()];
+    items.extend(ExportFormat::ALL.into_iter().map(|format| {
+        button(text(format.to_string()).size(12))
+            .style(button::secondary)
+            .padding([2, 6])
+            .on_press_maybe((count > 0).then_some(Message::Export(format)))
+            .into()
+    }));
+    items.push(
+        match status {
+            Some(Ok(path)) => text(format!("Saved to {path}")).size(12),
+            Some(Err(error)) => text(error.clone()).size(12).style(text::danger),
+            None => text(""),
+        }
+        .width(Fill)
+        .into(),
+    );
+    items.push(
+        button(text("Close").size(12))
+            .style(button::text)
+            .padding(0)
+            .on_press(Message::ToggleExport)
+            .into(),
+    );
+
+    row(items)
+        .spacing(8)
+        .padding([0, 10])
+        .align_y(iced::alignment::Vertical::Center)
+        .into()
+}
@@ -0,0 +1,46 @@
+use crate::app::Message;
+use iced::widget::{button, row, text, text_input};
+use iced::{Element, Fill};
+
+/// Inputs for importing a JSON, NDJSON or HAR file, with the outcome of the last import
+pub fn import_bar<'a>(
+    path: &str,
+    channel: &str,
+    status: Option<&Result<String, String>>,
+) -> Element<'a, Message> {
+    let can_import = !path.trim().is_empty();
+
+    let status = match status {
+        Some(Ok(summary)) => text(summary.clone()).size(12),
+        Some(Err(error)) => text(error.clone()).size(12).style(text::danger),
+        None => text("or drop a file on the window").size(12),
+    };
+
+    row![
+        text_input("File to import (JSON, NDJSON or HAR)", path)
+            .on_input(Message::ImportPathChanged)
+            .on_submit_maybe(can_import.then_some(Message::Import))
+            .size(14)
+            .padding(5)
+            .width(Fill),
+        text_input("Channel (default: file name)", channel)
+            .on_input(Message::ImportChannelChanged)
+            .on_submit_maybe(can_import.then_some(Message::Import))
+            .size(14)
+            .padding(5)
+            .width(220),
+        button(text("Import").size(12))
+            .style(button::secondary)
+            .padding([2, 6])
+            .on_press_maybe(can_import.then_some(Message::Import)),
+        status,
+        button(text("Close").size(12))
+            .style(button::text)
+            .padding(0)
+            .on_press(Message::ToggleImport),
+    ]
+    .spacing(8)
+    .padding([0, 10])
+    .align_y(iced::alignment::Vertical::Center)
+    .into()
+}
@@ -0,0 +1,28 @@
+use crate::app::Message;
+use iced::widget::{button, column, row, text};
+use iced::{Element, Fill};
+
+/// Banner for payloads received while paused, which stay hidden until shown
+pub fn held_banner<'a>(held: usize, paused: bool) -> Element<'a, Message> {
+    if held == 0 && !paused {
+        return column![].into();
+    }
+
+    let summary = match held {
+        0 => "Paused: new payloads are stored but not shown".to_string(),
+        1 => "1 new payload".to_string(),
+        count => format!("{count} new payloads"),
+    };
+
+    row![
+        text(summary).size(12).width(Fill),
+        button(text("Show").size(12))
+            .style(button::text)
+            .padding(0)
+            .on_press_maybe((held > 0).then_some(Message::ShowHeldPayloads)),
+    ]
+    .spacing(10)
+    .padding([0, 10])
+    .align_y(iced::alignment::Vertical::Center)
+    .into()
+}
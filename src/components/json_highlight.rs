@@ -1,10 +1,19 @@
 use crate::app::Message;
-use iced::widget::{column, row, text, button, svg};
-use iced::{Color, Element, Theme};
+use crate::clipboard::CopyTarget;
+use crate::search::{pointer_token, SearchQuery};
+use iced::widget::{column, container, row, text, button, scrollable, svg, Space};
+use iced::{Color, Element, Fill, Theme};
+use serde_json::Value;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use crate::components::styles;
 
-fn color_for_token(token: &str, is_key: bool, in_string: bool, theme: &Theme) -> Color {
+/// Height of every line in the JSON view, fixed so the visible lines can be computed from the scroll offset
+const LINE_HEIGHT: f32 = 22.0;
+/// Lines rendered above and below the viewport, so fast scrolling does not show blank space
+const OVERSCAN_LINES: usize = 30;
+
+pub(crate) fn color_for_token(token: &str, is_key: bool, in_string: bool, theme: &Theme) -> Color {
     let palette = theme.extended_palette();
     if in_string {
         if is_key {
@@ -26,173 +35,250 @@ fn color_for_token(token: &str, is_key: bool, in_string: bool, theme: &Theme) ->
     }
 }
 
-/// Calculates the number of lines hidden within each collapsible block.
-/// Returns a map where the key is the starting line index of a block
-/// and the value is the count of lines contained within that block.
-fn calculate_collapse_counts(lines: &[String]) -> HashMap<usize, usize> {
-    let mut collapse_counts = HashMap::new();
-    let mut indent_level: usize = 0;
-    let mut block_starts: Vec<(usize, usize)> = Vec::new();
-
-    for (idx, line_str) in lines.iter().enumerate() {
-        let trimmed = line_str.trim();
-
-        let starts_closing = trimmed.starts_with('}') || trimmed.starts_with(']');
-        let ends_opening = trimmed.ends_with('{') || trimmed.ends_with('[');
-
-        let indent_before_line = indent_level;
-
-        if starts_closing {
-            indent_level = indent_level.saturating_sub(1);
-            if let Some((_start_idx, start_indent)) = block_starts.last() {
-                if indent_level == *start_indent {
-                    let popped_start_idx = block_starts.pop().unwrap().0;
-                    let line_count = idx.saturating_sub(popped_start_idx).saturating_sub(1);
-                    collapse_counts.insert(popped_start_idx, line_count);
+/// A token of a JSON line: its text, whether it is an object key, and whether it is a quoted string
+type Token = (String, bool, bool);
+
+fn punctuation(c: &str) -> Token {
+    (c.to_string(), false, false)
+}
+
+/// A string as it appears between the quotes of its JSON encoding, so escapes
+/// such as `\n` keep every node on one line
+fn display_string(string: &str) -> String {
+    let encoded = serde_json::to_string(string).unwrap_or_default();
+    encoded[1..encoded.len() - 1].replace("\\\"", "\"")
+}
+
+/// A child node: its key when the parent is an object, its pointer token, and its value
+type Child<'a> = (Option<&'a str>, String, &'a Value);
+
+/// One line of the JSON view: the start of a node, or the closing bracket of an object or array
+#[derive(Debug)]
+struct Line {
+    indent: usize,
+    tokens: Vec<Token>,
+    /// JSON pointer of the node starting on this line; `None` for closing brackets
+    pointer: Option<String>,
+    /// For lines opening a non-empty object or array below the root, the index of its closing line
+    block_end: Option<usize>,
+}
+
+/// A payload's JSON laid out one node per line from the `Value` tree, tokenized
+/// once and reused by every render. Nodes are addressed by JSON pointer.
+#[derive(Debug)]
+pub struct HighlightedJson {
+    lines: Vec<Line>,
+}
+
+impl HighlightedJson {
+    pub fn new(value: &Value) -> Self {
+        let mut json = Self { lines: Vec::new() };
+        json.push_node(value, None, String::new(), 0, true);
+        json
+    }
+
+    /// Appends the lines of `value`, found under `key` in its parent object
+    fn push_node(
+        &mut self,
+        value: &Value,
+        key: Option<&str>,
+        pointer: String,
+        indent: usize,
+        last: bool,
+    ) {
+        let mut tokens = Vec::new();
+        if let Some(key) = key {
+            tokens.push((display_string(key), true, true));
+            tokens.push(punctuation(":"));
+        }
+
+        let (open, close, children): (&str, &str, Vec<Child>) = match value {
+            Value::Object(map) if !map.is_empty() => (
+                "{",
+                "}",
+                map.iter()
+                    .map(|(key, child)| (Some(key.as_str()), pointer_token(key), child))
+                    .collect(),
+            ),
+            Value::Array(items) if !items.is_empty() => (
+                "[",
+                "]",
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, child)| (None, index.to_string(), child))
+                    .collect(),
+            ),
+            scalar => {
+                match scalar {
+                    Value::Object(_) => tokens.extend([punctuation("{"), punctuation("}")]),
+                    Value::Array(_) => tokens.extend([punctuation("["), punctuation("]")]),
+                    Value::String(string) => tokens.push((display_string(string), false, true)),
+                    other => tokens.push((other.to_string(), false, false)),
                 }
+                if !last {
+                    tokens.push(punctuation(","));
+                }
+                self.lines.push(Line {
+                    indent,
+                    tokens,
+                    pointer: Some(pointer),
+                    block_end: None,
+                });
+                return;
             }
+        };
+
+        let start = self.lines.len();
+        tokens.push(punctuation(open));
+        self.lines.push(Line {
+            indent,
+            tokens,
+            pointer: Some(pointer.clone()),
+            block_end: None,
+        });
+
+        let count = children.len();
+        for (index, (child_key, token, child)) in children.into_iter().enumerate() {
+            self.push_node(
+                child,
+                child_key,
+                format!("{pointer}/{token}"),
+                indent + 1,
+                index + 1 == count,
+            );
         }
 
-        if ends_opening && idx != 0 {
-            block_starts.push((idx, indent_before_line));
+        let mut closing = vec![punctuation(close)];
+        if !last {
+            closing.push(punctuation(","));
         }
+        self.lines.push(Line {
+            indent,
+            tokens: closing,
+            pointer: None,
+            block_end: None,
+        });
 
-        if ends_opening {
-            indent_level += 1;
+        // The root spans the whole payload and is never collapsed
+        if start != 0 {
+            self.lines[start].block_end = Some(self.lines.len() - 1);
         }
     }
-    collapse_counts
-}
 
-pub fn highlight_json(
-    json: &str,
-    theme: &Theme,
-    collapsed_lines: &HashSet<usize>,
-) -> Element<'static, Message> {
-    let lines = json.lines().map(|line| line.to_owned()).collect::<Vec<_>>();
-    let collapse_counts = calculate_collapse_counts(&lines);
+    /// Indexes of the lines shown once the `collapsed` nodes are folded to their opening line
+    fn visible_lines(&self, collapsed: &HashSet<String>) -> Vec<usize> {
+        let mut visible = Vec::with_capacity(self.lines.len());
+        let mut idx = 0;
+        while idx < self.lines.len() {
+            visible.push(idx);
+            let line = &self.lines[idx];
+            idx = match (line.block_end, &line.pointer) {
+                (Some(end), Some(pointer)) if collapsed.contains(pointer) => end + 1,
+                _ => idx + 1,
+            };
+        }
+        visible
+    }
+}
 
-    let mut elements = Vec::new();
-    let mut indent_level: usize = 0;
-    let indent_size = 2;
-    let mut skip_depth: Option<usize> = None;
+/// Tokenized JSON of every expanded payload, keyed by payload id. Filled in `update`,
+/// so rendering and scrolling only ever read it.
+#[derive(Debug, Default)]
+pub struct HighlightCache {
+    entries: HashMap<String, Arc<HighlightedJson>>,
+}
 
-    for (idx, line) in lines.into_iter().enumerate() {
-        let trimmed_line = line.trim();
+impl HighlightCache {
+    pub fn get(&self, payload_id: &str) -> Option<Arc<HighlightedJson>> {
+        self.entries.get(payload_id).cloned()
+    }
 
-        if let Some(depth) = skip_depth {
-            if indent_level > depth {
-                if trimmed_line.starts_with('}') || trimmed_line.starts_with(']') {
-                    indent_level = indent_level.saturating_sub(1);
-                }
-                if trimmed_line.ends_with('{') || trimmed_line.ends_with('[') {
-                    indent_level += 1;
-                }
-                continue;
-            } else {
-                skip_depth = None;
-            }
+    /// Keeps exactly the `expanded` payloads, given as id and value: those not cached
+    /// yet are tokenized, and those collapsed or deleted since are dropped
+    pub fn sync<'a>(&mut self, expanded: impl IntoIterator<Item = (&'a str, &'a Value)>) {
+        let mut entries = HashMap::new();
+        for (id, value) in expanded {
+            let json = self
+                .entries
+                .remove(id)
+                .unwrap_or_else(|| Arc::new(HighlightedJson::new(value)));
+            entries.insert(id.to_string(), json);
         }
+        self.entries = entries;
+    }
+}
 
-        let current_indent = indent_level;
-        if trimmed_line.starts_with('}') || trimmed_line.starts_with(']') {
-            indent_level = indent_level.saturating_sub(1);
-        }
+/// How the expanded payloads' JSON views are shown
+pub struct JsonViewState<'a> {
+    /// JSON pointers of the collapsed nodes, per expanded payload id
+    pub collapsed_nodes: &'a HashMap<String, HashSet<String>>,
+    /// Payload id and JSON pointer of the node selected for copying
+    pub selected_node: Option<&'a (String, String)>,
+    /// Vertical scroll offsets of the JSON views, per payload id
+    pub scroll_offsets: &'a HashMap<String, f32>,
+    /// Tallest the JSON view grows before it scrolls
+    pub max_height: f32,
+    pub cache: &'a HighlightCache,
+    /// JSON pointers a path search selects, per matching payload id
+    pub path_hits: &'a HashMap<String, HashSet<String>>,
+}
 
-        let is_collapsible = (trimmed_line.ends_with('{') || trimmed_line.ends_with('[')) && idx != 0;
-        let is_collapsed = collapsed_lines.contains(&idx);
+/// Small text button for the copy actions of the selected node
+fn copy_button(label: &str, message: Message) -> Element<'static, Message> {
+    button(text(label.to_string()).size(10))
+        .style(button::secondary)
+        .padding([0, 4])
+        .on_press(message)
+        .into()
+}
 
-        let mut is_key = true;
-        let mut in_string = false;
-        let mut current_token = String::new();
-        let mut tokens = Vec::new();
-        let mut prev_char = '\0'; // Track previous char for escapes
-
-        if !(is_collapsed && is_collapsible && trimmed_line.is_empty()) {
-            for c in trimmed_line.chars() {
-                if c == '"' {
-                    if in_string && prev_char != '\\' {
-                        // End of string (unescaped quote)
-                        tokens.push((current_token.clone(), is_key, true));
-                        current_token.clear();
-                        in_string = false;
-                        // Delimiter logic below will set is_key if appropriate
-                    } else if !in_string {
-                        // Start of string
-                        // Push any pending non-string token before starting the string
-                        if !current_token.is_empty() { // Simplified check
-                            tokens.push((current_token.clone(), is_key, false));
-                            current_token.clear();
-                        }
-                        in_string = true;
-                        // Don't add the quote to current_token
-                    } else {
-                        // Inside string, and quote char itself
-                        if prev_char == '\\' {
-                            // It's an escaped quote: remove the preceding \ and add "
-                            current_token.pop(); // Remove the already added \
-                            current_token.push(c); // Add the "
-                        } else {
-                             // An unescaped quote char within a string? Technically invalid JSON,
-                             // but we'll treat it like a regular char.
-                             current_token.push(c);
-                        }
-                    }
-                } else if in_string {
-                    // Regular character inside string
-                    current_token.push(c);
+/// How a line is shown, beyond its tokens
+struct LineState {
+    collapsed: bool,
+    selected: bool,
+    /// Whether a path query matched the node starting on this line
+    hit: bool,
+}
+
+fn render_line(
+    line: &Line,
+    idx: usize,
+    theme: &Theme,
+    state: LineState,
+    search: Option<&SearchQuery>,
+    payload_id: &str,
+) -> Element<'static, Message> {
+    let is_collapsible = line.block_end.is_some();
+    let indent_size = 2;
+
+    let row_element = row(
+        line.tokens
+            .iter()
+            .map(|(token, is_key, in_string)| {
+                let color = color_for_token(token, *is_key, *in_string, theme);
+                let is_punctuation = !in_string && ["{", "}", "[", "]", ":", ","].contains(&token.as_str());
+                let highlighted = (state.hit && !is_punctuation)
+                    || search.is_some_and(|query| query.highlights(token, *is_key));
+                let token_text = text(token.clone())
+                    .style(move |_| iced::widget::text::Style { color: Some(color) });
+
+                if highlighted {
+                    container(token_text).style(styles::container_highlight).into()
                 } else {
-                    // Character outside string
-                    // Push any accumulated non-string token before handling the delimiter/char
-                    if !current_token.is_empty() { // Simplified check
-                        tokens.push((current_token.clone(), is_key, false));
-                        current_token.clear();
-                    }
-                    // Handle the current delimiter or start of a new non-string token
-                    if ['{', '}', '[', ']', ':', ','].contains(&c) {
-                        tokens.push((c.to_string(), false, false));
-                        if c == ',' || c == '{' || c == '[' {
-                            is_key = true; // Next token *could* be a key
-                        } else if c == ':'{
-                             is_key = false; // Next token must be a value
-                        }
-                    } else if !c.is_whitespace() {
-                        // Start accumulating a non-string token (number, bool, null)
-                        current_token.push(c);
-                    }
-                    // Ignore whitespace outside strings
+                    token_text.into()
                 }
-                prev_char = c; // Update prev_char for the next iteration
-            }
-            // After the loop, push any remaining token
-            if !current_token.is_empty() { // Simplified check
-                tokens.push((current_token, is_key, false));
-            }
-        }
+            })
+            .collect::<Vec<Element<'_, Message>>>(),
+    );
 
-        let row_element = row(
-            tokens
-                .into_iter()
-                .map(|(token, is_key, in_string)| {
-                    let color = color_for_token(&token, is_key, in_string, theme);
-                    text(token)
-                        .style(move |_| iced::widget::text::Style { color: Some(color) })
-                        .into()
-                })
-                .collect::<Vec<Element<'_, Message>>>(),
-        );
-
-        let collapse_button_icon = if is_collapsible {
-            if is_collapsed {
+    let collapse_element: Element<'_, Message> = match (&line.pointer, is_collapsible) {
+        (Some(pointer), true) => {
+            let collapse_button_icon = if state.collapsed {
                 include_bytes!("../../assets/icons/mdi--caret-up.svg").as_slice()
             } else {
                 include_bytes!("../../assets/icons/mdi--caret-down.svg").as_slice()
-            }
-        } else {
-            &[]
-        };
-
-        let collapse_element: Element<'_, Message> = if is_collapsible {
+            };
             button(
                 svg(svg::Handle::from_memory(collapse_button_icon))
                     .style(styles::svg_style_secondary),
@@ -200,24 +286,37 @@ pub fn highlight_json(
             .width(15)
             .padding(0)
             .style(button::secondary)
-            .on_press(Message::ToggleJsonSection(idx))
+            .on_press(Message::ToggleJsonSection(payload_id.to_string(), pointer.clone()))
             .into()
-        } else {
-            text(" ").width(15).into()
-        };
+        }
+        _ => text(" ").width(15).into(),
+    };
 
-        let indented_row = row![
-            collapse_element,
-            text(format!("{:>3} ", idx + 1))
-                .size(12)
-                .style(move |theme: &Theme| iced::widget::text::Style {
-                    color: Some(theme.extended_palette().background.strong.color),
-                })
-                .width(30),
-            text(" ".repeat(current_indent * indent_size)),
-            if is_collapsible && is_collapsed {
-                let count = collapse_counts.get(&idx).copied().unwrap_or(0);
-                let closing_char = if trimmed_line.ends_with('{') { "}" } else { "]" };
+    // Closing brackets start no node, so only other lines can be selected for copying
+    let line_number = button(
+        text(format!("{:>3} ", idx + 1))
+            .size(12)
+            .style(move |theme: &Theme| iced::widget::text::Style {
+                color: Some(theme.extended_palette().background.strong.color),
+            }),
+    )
+    .width(30)
+    .padding(0)
+    .style(button::text)
+    .on_press_maybe(
+        line.pointer
+            .clone()
+            .map(|pointer| Message::SelectJsonNode(payload_id.to_string(), pointer)),
+    );
+
+    let indented_row = row![
+        collapse_element,
+        line_number,
+        text(" ".repeat(line.indent * indent_size)),
+        match line.block_end.filter(|_| state.collapsed) {
+            Some(end) => {
+                let count = end - idx - 1;
+                let closing_char = if line.tokens.last().is_some_and(|(token, _, _)| token == "{") { "}" } else { "]" };
                 let token_color = color_for_token(closing_char, false, false, theme);
                 let count_color = theme.extended_palette().background.strong.color;
 
@@ -229,23 +328,77 @@ pub fn highlight_json(
                 ];
 
                 row![row_element, count_indicator]
-            } else {
-                row_element
             }
-        ];
-
-        elements.push(indented_row.into());
+            None => row_element,
+        }
+    ];
 
-        if is_collapsible && is_collapsed {
-            skip_depth = Some(current_indent);
-            if trimmed_line.ends_with('{') || trimmed_line.ends_with('[') {
-                indent_level += 1;
-            }
-        } else if trimmed_line.ends_with('{') || trimmed_line.ends_with('[') {
-            indent_level += 1;
+    match line.pointer.as_ref().filter(|_| state.selected) {
+        Some(pointer) => {
+            let copy_label = if is_collapsible { "Copy block" } else { "Copy value" };
+            let selected_row = row![
+                indented_row,
+                text("  "),
+                copy_button(copy_label, Message::Copy(payload_id.to_string(), CopyTarget::Value(pointer.clone()))),
+                copy_button("Copy path", Message::Copy(payload_id.to_string(), CopyTarget::Path(pointer.clone()))),
+            ]
+            .spacing(4);
+            container(selected_row)
+                .height(LINE_HEIGHT)
+                .style(styles::container_selected_line)
+                .into()
         }
+        None => container(indented_row).height(LINE_HEIGHT).into(),
     }
-
-    column(elements).spacing(2).into()
 }
 
+/// Renders a payload's JSON tree with collapsible objects and arrays inside its own
+/// scrollable, building widgets only for the lines in or near the viewport. Clicking
+/// a line number selects that node, offering to copy it or its JSONPath.
+pub fn highlight_json(
+    json: &HighlightedJson,
+    theme: &Theme,
+    search: Option<&SearchQuery>,
+    payload_id: &str,
+    state: &JsonViewState<'_>,
+) -> Element<'static, Message> {
+    let no_collapsed_nodes = HashSet::new();
+    let collapsed_nodes = state.collapsed_nodes.get(payload_id).unwrap_or(&no_collapsed_nodes);
+    let visible = json.visible_lines(collapsed_nodes);
+    let content_height = visible.len() as f32 * LINE_HEIGHT;
+    let height = content_height.min(state.max_height);
+    let offset = state.scroll_offsets.get(payload_id).copied().unwrap_or(0.0);
+    let selected_node = state
+        .selected_node
+        .filter(|(id, _)| id == payload_id)
+        .map(|(_, pointer)| pointer);
+    let no_path_hits = HashSet::new();
+    let path_hits = state.path_hits.get(payload_id).unwrap_or(&no_path_hits);
+
+    let first = ((offset / LINE_HEIGHT) as usize).saturating_sub(OVERSCAN_LINES).min(visible.len());
+    let last = (first + (height / LINE_HEIGHT).ceil() as usize + 2 * OVERSCAN_LINES).min(visible.len());
+
+    let mut elements = Vec::with_capacity(last - first + 2);
+    elements.push(Space::with_height(first as f32 * LINE_HEIGHT).into());
+    for &idx in &visible[first..last] {
+        let line = &json.lines[idx];
+        let pointer = line.pointer.as_ref();
+        let state = LineState {
+            collapsed: pointer.is_some_and(|pointer| collapsed_nodes.contains(pointer)),
+            selected: pointer.is_some() && pointer == selected_node,
+            hit: pointer.is_some_and(|pointer| path_hits.contains(pointer)),
+        };
+        elements.push(render_line(line, idx, theme, state, search, payload_id));
+    }
+    elements.push(Space::with_height((visible.len() - last) as f32 * LINE_HEIGHT).into());
+
+    let id = payload_id.to_string();
+    scrollable(column(elements).width(Fill))
+        .direction(scrollable::Direction::Vertical(
+            scrollable::Scrollbar::new().width(5).scroller_width(5),
+        ))
+        .on_scroll(move |viewport| Message::JsonScrolled(id.clone(), viewport.absolute_offset().y))
+        .height(height)
+        .width(Fill)
+        .into()
+}
@@ -1,9 +1,28 @@
+pub(crate) mod body_views;
+pub mod channels;
+pub mod diff;
+pub mod editor;
+pub mod export;
+pub mod import;
+pub mod incoming;
 pub(crate) mod json_highlight;
 pub mod modal;
 pub mod payloads;
+pub mod retention;
+pub mod search;
+pub mod server_status;
 pub mod settings;
 pub(crate) mod styles;
 
+pub use channels::channel_tabs;
+pub use diff::{compare_banner, diff_view};
+pub use editor::editor_error;
+pub use export::export_bar;
+pub use import::import_bar;
+pub use incoming::held_banner;
 pub use modal::modal;
 pub use payloads::payload_list;
+pub use retention::eviction_notice;
+pub use search::search_bar;
+pub use server_status::server_error;
 pub use settings::settings_modal;
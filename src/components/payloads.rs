@@ -1,151 +1,308 @@
-use crate::components::json_highlight::highlight_json;
+use crate::components::body_views::{form_view, hex_view, text_view};
+use crate::components::json_highlight::{highlight_json, JsonViewState};
 use crate::components::styles;
 use crate::app::Message;
+use crate::clipboard::CopyTarget;
+use crate::payload::{Annotations, Origin, Payload, PayloadKind, DEFAULT_CHANNEL};
+use crate::search::SearchQuery;
 use chrono::{DateTime, Utc};
 use core::time::Duration;
 use iced::widget::{button, column, container, row, scrollable, stack, svg, text};
 use iced::{Element, Fill, Theme};
 use millisecond::prelude::*;
-use std::collections::HashSet;
 use serde_json::Value;
+use std::collections::HashSet;
+
+/// Converts a receive time into a human-readable relative time string
+fn human_readable_time(received_at: DateTime<Utc>) -> String {
+    let duration = Utc::now().signed_duration_since(received_at);
+    Duration::from_millis(duration.num_milliseconds().max(0) as u64).relative()
+}
+
+/// Small channel label for rows, omitted for the default channel
+fn channel_badge<'a>(channel: &str) -> Element<'a, Message> {
+    if channel == DEFAULT_CHANNEL {
+        return row![].into();
+    }
+
+    container(text(channel.to_string()).size(10.0))
+        .padding([1, 4])
+        .style(styles::container_badge)
+        .into()
+}
+
+/// Severity, label and tag badges from the sender's envelope, empty without annotations
+fn annotation_badges<'a>(annotations: &Annotations) -> Element<'a, Message> {
+    let mut badges: Vec<Element<'a, Message>> = Vec::new();
+    if let Some(severity) = annotations.severity {
+        badges.push(
+            container(text(severity.as_str()).size(10.0))
+                .padding([1, 4])
+                .style(styles::container_severity(severity))
+                .into(),
+        );
+    }
+    let color = annotations.color.as_deref().and_then(styles::parse_color);
+    // A color without a label still marks the row, as a blank swatch
+    let label = annotations.label.clone().or_else(|| color.map(|_| "  ".to_string()));
+    if let Some(label) = label {
+        badges.push(
+            container(text(label).size(10.0))
+                .padding([1, 4])
+                .style(styles::container_label(color))
+                .into(),
+        );
+    }
+    badges.extend(annotations.tags.iter().map(|tag| {
+        container(text(format!("#{tag}")).size(10.0))
+            .padding([1, 4])
+            .style(styles::container_badge)
+            .into()
+    }));
 
-/// Converts a timestamp ID into a human-readable relative time string
-fn human_readable_time(id: &str) -> String {
+    row(badges)
+        .spacing(4)
+        .align_y(iced::alignment::Vertical::Center)
+        .into()
+}
+
+/// Where the payload was sent from, as a link opening it in the configured editor.
+/// Collapsed rows show only the file name; expanded payloads show the whole path.
+fn origin_link<'a>(id: &str, origin: Option<&Origin>, full_path: bool) -> Element<'a, Message> {
+    let Some(origin) = origin else {
+        return row![].into();
+    };
+    let location = if full_path {
+        origin.to_string()
+    } else {
+        let file_name = origin.file.rsplit(['/', '\\']).next().unwrap_or(&origin.file);
+        Origin {
+            file: file_name.to_string(),
+            line: origin.line,
+        }
+        .to_string()
+    };
+
+    button(text(location).size(10.0).style(|theme: &Theme| text::Style {
+        color: Some(theme.extended_palette().primary.base.color),
+    }))
+    .style(button::text)
+    .padding([1, 0])
+    .on_press(Message::OpenOrigin(id.to_string()))
+    .into()
+}
 
-    id.parse::<i64>()
-        .ok()
-        .and_then(DateTime::<Utc>::from_timestamp_millis)
-        .map(|time| Utc::now().signed_duration_since(time)).map_or_else(|| "Invalid timestamp".to_string(), |duration| Duration::from_millis(duration.num_milliseconds() as u64).relative())
+/// Toggles whether a payload is pinned; pinned payloads survive clears and eviction
+fn pin_button<'a>(id: &str, pinned: bool) -> Element<'a, Message> {
+    let icon = if pinned {
+        include_bytes!("../../assets/icons/mdi--pin.svg").as_slice()
+    } else {
+        include_bytes!("../../assets/icons/mdi--pin-outline.svg").as_slice()
+    };
 
+    button(
+        svg(svg::Handle::from_memory(icon))
+            .width(Fill)
+            .height(Fill)
+            .style(if pinned { styles::svg_style_primary } else { styles::svg_style_secondary }),
+    )
+    .style(if pinned { button::primary } else { button::secondary })
+    .width(18)
+    .height(18)
+    .padding(1)
+    .on_press(Message::TogglePin(id.to_string()))
+    .into()
+}
+
+/// Heading separating the pinned payloads from the rest
+fn section_label<'a>(label: &'a str) -> Element<'a, Message> {
+    text(label)
+        .size(12)
+        .style(|theme: &Theme| text::Style {
+            color: Some(theme.extended_palette().background.strong.color),
+        })
+        .into()
 }
 
 /// Creates a scrollable display of received JSON payloads using cached data
 pub fn payload_list<'a>(
-    payloads: &'a [(String, Value)],
-    expanded_id: Option<&String>,
+    payloads: Vec<&'a Payload>,
+    expanded_ids: &HashSet<String>,
     theme: &Theme,
-    collapsed_json_lines: &HashSet<usize>,
+    json_view: JsonViewState<'_>,
+    search: Option<&SearchQuery>,
 ) -> Element<'a, Message> {
-    let storage_rows = column(
-        payloads
-            .iter()
-            .map(|(id, value)| {
-                let is_expanded = expanded_id == Some(id);
-                let timestamp = human_readable_time(id);
-
-                if is_expanded {
-                    let pretty_json = serde_json::to_string_pretty(value).unwrap_or_else(|err| {
-                        eprintln!("Error prettifying payload {id}: {err}");
-                        format!("{{ \"error\": \"Failed to render JSON: {err}\" }}")
-                    });
-
-                    let highlighted_json = highlight_json(
-                        &pretty_json,
-                        theme,
-                        collapsed_json_lines,
-                    );
-
-                    let close_svg = svg(svg::Handle::from_memory(
-                        include_bytes!("../../assets/icons/mdi--caret-down.svg").as_slice(),
-                    ))
-                    .width(Fill)
-                    .height(Fill)
-                    .style(styles::svg_style_secondary);
-
-                    let delete_svg = svg(svg::Handle::from_memory(
-                        include_bytes!("../../assets/icons/mdi--trash-can.svg").as_slice(),
-                    ))
-                    .width(Fill)
-                    .height(Fill)
-                    .style(styles::svg_style_primary);
-
-                    container(
-                        stack![
-                            highlighted_json,
-                            container(row![
-                                container(text(timestamp).size(10.0))
-                                    .padding(3.0)
-                                    .align_x(iced::alignment::Horizontal::Right)
-                                    .align_y(iced::alignment::Vertical::Bottom)
-                                    .width(Fill),
-                                button(delete_svg)
-                                    .style(button::danger)
-                                    .width(18)
-                                    .height(18)
-                                    .padding(1)
-                                    .on_press(Message::DeletePayload(id.clone())),
-                                button(close_svg)
-                                    .width(18)
-                                    .height(18)
-                                    .padding(0)
-                                    .on_press(Message::TogglePayload(id.clone()))
-                            ].spacing(5))
-                            .align_top(Fill)
-                            .align_right(Fill)
-                            .width(Fill),
-                        ]
-                        .width(Fill),
-                    )
-                    .padding(10)
-                    .width(Fill)
-                    .style(styles::container_code)
-                    .into()
-                } else {
-                    let expand_svg = svg(svg::Handle::from_memory(
-                        include_bytes!("../../assets/icons/mdi--caret-up.svg").as_slice(),
-                    ))
-                    .width(Fill)
-                    .height(Fill)
-                    .style(styles::svg_style_secondary);
-
-                    let delete_svg = svg(svg::Handle::from_memory(
-                        include_bytes!("../../assets/icons/mdi--trash-can.svg").as_slice(),
-                    ))
-                    .width(Fill)
-                    .height(Fill)
-                    .style(styles::svg_style_primary);
-
-                    button(
+    let payload_row = |payload: &'a Payload| -> Element<'a, Message> {
+        let Payload { id, value, kind, received_at, request, pinned, annotations, .. } = payload;
+        let is_expanded = expanded_ids.contains(id);
+        let timestamp = human_readable_time(*received_at);
+
+        if is_expanded {
+            let body_view = match (kind, value) {
+                (PayloadKind::Text, Value::String(body)) => text_view(body, search),
+                (PayloadKind::Binary, Value::String(hex)) => hex_view(hex),
+                (PayloadKind::Form, fields) => form_view(fields, search),
+                // Tokenized in `update` when the payload was expanded, never while rendering
+                _ => match json_view.cache.get(id) {
+                    Some(json) => highlight_json(&json, theme, search, id, &json_view),
+                    None => text("Preparing view").size(12).into(),
+                },
+            };
+
+            let close_svg = svg(svg::Handle::from_memory(
+                include_bytes!("../../assets/icons/mdi--caret-down.svg").as_slice(),
+            ))
+            .width(Fill)
+            .height(Fill)
+            .style(styles::svg_style_secondary);
+
+            let delete_svg = svg(svg::Handle::from_memory(
+                include_bytes!("../../assets/icons/mdi--trash-can.svg").as_slice(),
+            ))
+            .width(Fill)
+            .height(Fill)
+            .style(styles::svg_style_primary);
+
+            let request_summary = request.as_ref().map(|request| {
+                text(request.summary())
+                    .size(10.0)
+                    .style(|theme: &Theme| text::Style {
+                        color: Some(theme.extended_palette().background.strong.color),
+                    })
+            });
+
+            container(
+                stack![
+                    column![request_summary, body_view].spacing(5),
+                    container(row![
                         container(
                             row![
-                                container(text(format!("{value}")).size(14).height(18.0)).width(Fill),
-                                container(text(timestamp).size(10.0))
-                                    .padding(4.0)
-                                    .align_x(iced::alignment::Horizontal::Right)
-                                    .align_y(iced::alignment::Vertical::Center),
-                                button(delete_svg)
-                                    .style(button::danger)
-                                    .width(18)
-                                    .height(18)
-                                    .padding(1)
-                                    .on_press(Message::DeletePayload(id.clone())),
-                                container(expand_svg)
-                                    .width(18)
-                                    .height(18)
-                                    .padding(0)
+                                origin_link(id, annotations.origin.as_ref(), true),
+                                annotation_badges(annotations),
+                                channel_badge(&payload.channel),
+                                text(timestamp).size(10.0),
                             ]
-                            .spacing(5)
+                            .spacing(5),
                         )
-                        .padding(10)
-                        .width(Fill)
-                        .style(styles::container_code_closed)
-                    )
-                    .style(button::text)
-                    .width(Fill)
-                    .on_press(Message::TogglePayload(id.clone()))
-                    .padding(0)
-                    .into()
-                }
-            })
-            .collect::<Vec<_>>(),
-    )
-    .spacing(10)
-    .padding(iced_core::Padding {
-        right: 5.0,
-        left: 5.0,
-        top: 1.0,
-        bottom: 0.0,
-    });
+                            .padding(3.0)
+                            .align_x(iced::alignment::Horizontal::Right)
+                            .align_y(iced::alignment::Vertical::Bottom)
+                            .width(Fill),
+                        button(text("Copy").size(10))
+                            .style(button::secondary)
+                            .height(18)
+                            .padding([1, 4])
+                            .on_press(Message::Copy(id.clone(), CopyTarget::Pretty)),
+                        if matches!(kind, PayloadKind::Json | PayloadKind::Form) {
+                            button(text("Copy compact").size(10))
+                                .style(button::secondary)
+                                .height(18)
+                                .padding([1, 4])
+                                .on_press(Message::Copy(id.clone(), CopyTarget::Compact))
+                                .into()
+                        } else {
+                            Element::from(row![])
+                        },
+                        button(text("Diff previous").size(10))
+                            .style(button::secondary)
+                            .height(18)
+                            .padding([1, 4])
+                            .on_press(Message::DiffWithPrevious(id.clone())),
+                        button(delete_svg)
+                            .style(button::danger)
+                            .width(18)
+                            .height(18)
+                            .padding(1)
+                            .on_press(Message::DeletePayload(id.clone())),
+                        pin_button(id, *pinned),
+                        button(close_svg)
+                            .width(18)
+                            .height(18)
+                            .padding(0)
+                            .on_press(Message::TogglePayload(id.clone()))
+                    ].spacing(5))
+                    .align_top(Fill)
+                    .align_right(Fill)
+                    .width(Fill),
+                ]
+                .width(Fill),
+            )
+            .padding(10)
+            .width(Fill)
+            .style(styles::container_code)
+            .into()
+        } else {
+            let expand_svg = svg(svg::Handle::from_memory(
+                include_bytes!("../../assets/icons/mdi--caret-up.svg").as_slice(),
+            ))
+            .width(Fill)
+            .height(Fill)
+            .style(styles::svg_style_secondary);
+
+            let delete_svg = svg(svg::Handle::from_memory(
+                include_bytes!("../../assets/icons/mdi--trash-can.svg").as_slice(),
+            ))
+            .width(Fill)
+            .height(Fill)
+            .style(styles::svg_style_primary);
+
+            button(
+                container(
+                    row![
+                        annotation_badges(annotations),
+                        container(text(payload.preview()).size(14).height(18.0)).width(Fill),
+                        origin_link(id, annotations.origin.as_ref(), false),
+                        channel_badge(&payload.channel),
+                        container(text(timestamp).size(10.0))
+                            .padding(4.0)
+                            .align_x(iced::alignment::Horizontal::Right)
+                            .align_y(iced::alignment::Vertical::Center),
+                        button(delete_svg)
+                            .style(button::danger)
+                            .width(18)
+                            .height(18)
+                            .padding(1)
+                            .on_press(Message::DeletePayload(id.clone())),
+                        pin_button(id, *pinned),
+                        container(expand_svg)
+                            .width(18)
+                            .height(18)
+                            .padding(0)
+                    ]
+                    .spacing(5)
+                )
+                .padding(10)
+                .width(Fill)
+                .style(styles::container_code_closed)
+            )
+            .style(button::text)
+            .width(Fill)
+            .on_press(Message::TogglePayload(id.clone()))
+            .padding(0)
+            .into()
+        }
+    };
+
+    // Pinned payloads get their own section above the rest
+    let (pinned, unpinned): (Vec<_>, Vec<_>) = payloads.into_iter().partition(|payload| payload.pinned);
+    let mut rows = Vec::new();
+    if !pinned.is_empty() {
+        rows.push(section_label("Pinned"));
+        rows.extend(pinned.into_iter().map(payload_row));
+        if !unpinned.is_empty() {
+            rows.push(section_label("Recent"));
+        }
+    }
+    rows.extend(unpinned.into_iter().map(payload_row));
+
+    let storage_rows = column(rows)
+        .spacing(10)
+        .padding(iced_core::Padding {
+            right: 5.0,
+            left: 5.0,
+            top: 1.0,
+            bottom: 0.0,
+        });
 
     scrollable(container(storage_rows).padding(iced_core::Padding {
         right: 5.0,
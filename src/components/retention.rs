@@ -0,0 +1,67 @@
+use crate::app::Message;
+use crate::settings::Retention;
+use iced::widget::{button, column, row, text};
+use iced::{Element, Fill};
+
+/// Formats a byte count with the largest unit that keeps it above one
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Summarizes the global retention limits, e.g. "2.0 MiB, 500 payloads"
+pub fn describe(retention: &Retention) -> String {
+    let mut limits = Vec::new();
+    if let Some(bytes) = retention.max_bytes {
+        limits.push(format_bytes(bytes));
+    }
+    if let Some(count) = retention.max_count {
+        limits.push(format!("{count} payloads"));
+    }
+    if let Some(secs) = retention.max_age_secs {
+        limits.push(format!("{:.1} hours", secs as f64 / 3600.0));
+    }
+    if !retention.channels.is_empty() {
+        limits.push(format!("{} channel limits", retention.channels.len()));
+    }
+
+    if limits.is_empty() {
+        "no limits".to_string()
+    } else {
+        limits.join(", ")
+    }
+}
+
+/// Shows how many payloads the retention policy dropped, nothing when none were
+pub fn eviction_notice<'a>(evicted: usize, retention: &Retention) -> Element<'a, Message> {
+    if evicted == 0 {
+        return column![].into();
+    }
+
+    row![
+        text(format!(
+            "{evicted} older payload{} dropped by the retention policy ({})",
+            if evicted == 1 { " was" } else { "s were" },
+            describe(retention)
+        ))
+        .size(12)
+        .width(Fill),
+        button(text("Dismiss").size(12))
+            .style(button::text)
+            .padding(0)
+            .on_press(Message::DismissEvictions),
+    ]
+    .padding([0, 10])
+    .align_y(iced::alignment::Vertical::Center)
+    .into()
+}
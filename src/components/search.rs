@@ -0,0 +1,29 @@
+use crate::app::Message;
+use iced::widget::{row, text, text_input};
+use iced::{Element, Fill};
+
+/// Creates the search bar with a match counter, or the parse error for invalid expressions
+pub fn search_bar<'a>(
+    input: &str,
+    error: Option<&str>,
+    matches: Option<(usize, usize)>,
+) -> Element<'a, Message> {
+    let status = match (error, matches) {
+        (Some(error), _) => text(error.to_string()).size(12).style(text::danger),
+        (None, Some((matching, total))) => text(format!("{matching} / {total}")).size(12),
+        (None, None) => text(""),
+    };
+
+    row![
+        text_input("Filter: text, key=value or $.path == value", input)
+            .on_input(Message::SearchChanged)
+            .size(14)
+            .padding(5)
+            .width(Fill),
+        status,
+    ]
+    .spacing(10)
+    .padding([0, 10])
+    .align_y(iced::alignment::Vertical::Center)
+    .into()
+}
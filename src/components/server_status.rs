@@ -0,0 +1,19 @@
+use crate::app::Message;
+use crate::server::ServerStatus;
+use iced::widget::{column, container, text};
+use iced::{Element, Fill};
+
+/// Shows a banner when the payload server failed to start, nothing otherwise
+pub fn server_error<'a>(status: &ServerStatus) -> Element<'a, Message> {
+    match status {
+        ServerStatus::Failed(error) => container(
+            text(format!("Payload server is not running: {error}"))
+                .size(12)
+                .style(text::danger),
+        )
+        .padding([0, 10])
+        .width(Fill)
+        .into(),
+        _ => column![].into(),
+    }
+}
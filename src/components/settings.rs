@@ -1,9 +1,186 @@
-use crate::app::Message;
-use iced::widget::{column, container, radio, scrollable, text};
+use crate::app::{Message, RetentionInputs};
+use crate::components::retention;
+use crate::editor;
+use crate::server::{ListenAddress, ServerStatus};
+use crate::settings::{Expansion, Retention, Settings};
+use iced::widget::{button, checkbox, column, container, radio, row, scrollable, text, text_input};
 use iced::{Element, Fill, Theme};
 
-/// Creates the settings modal content with theme selection
-pub fn settings_modal<'a>(current_theme: Theme) -> Element<'a, Message> {
+/// Creates the payload server section showing the active address and editable host/port
+fn server_section<'a>(
+    active_address: &ListenAddress,
+    status: &ServerStatus,
+    host_input: &str,
+    port_input: &str,
+) -> Element<'a, Message> {
+    let status_text = match status {
+        ServerStatus::Starting => text(format!("Starting on http://{active_address}")),
+        ServerStatus::Listening(bound) => text(format!("Listening on http://{bound}")),
+        ServerStatus::Failed(error) => text(error.clone()).style(text::danger),
+    };
+
+    let port_valid = port_input.trim().parse::<u16>().is_ok();
+    let input_valid = port_valid && !host_input.trim().is_empty();
+    let overridden = active_address.host != host_input.trim()
+        || active_address.port.to_string() != port_input.trim();
+
+    column![
+        text("Payload Server").size(18),
+        status_text.size(12),
+        row![
+            text_input("Host", host_input)
+                .on_input(Message::ListenHostChanged)
+                .width(Fill),
+            text_input("Port", port_input)
+                .on_input(Message::ListenPortChanged)
+                .width(80),
+            button(text("Apply").size(14))
+                .on_press_maybe(input_valid.then_some(Message::ApplyListenAddress)),
+        ]
+        .spacing(5),
+        if !port_valid {
+            text("Port must be a number between 0 and 65535").size(12).style(text::danger)
+        } else if overridden {
+            text("Active address differs: overridden by --host/--port, DBUG_HOST/DBUG_PORT, or not yet applied").size(12)
+        } else {
+            text("")
+        },
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// Creates the retention section with editable global limits
+fn retention_section<'a>(inputs: &RetentionInputs, current: &Retention) -> Element<'a, Message> {
+    let parsed = inputs.parse(current);
+    let status = match &parsed {
+        Err(error) => text(error.clone()).size(12).style(text::danger),
+        Ok(retention) if retention != current => text("Not yet applied").size(12),
+        Ok(_) => text(format!("Keeping {}; pinned payloads are never evicted", retention::describe(current))).size(12),
+    };
+
+    column![
+        text("Retention").size(18),
+        row![
+            text_input("MiB", &inputs.size)
+                .on_input(Message::RetentionSizeChanged)
+                .width(Fill),
+            text_input("Payloads", &inputs.count)
+                .on_input(Message::RetentionCountChanged)
+                .width(Fill),
+            text_input("Hours", &inputs.age)
+                .on_input(Message::RetentionAgeChanged)
+                .width(Fill),
+            button(text("Apply").size(14))
+                .on_press_maybe(parsed.is_ok().then_some(Message::ApplyRetention)),
+        ]
+        .spacing(5),
+        status,
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// Creates the incoming section: what happens when a payload arrives
+fn incoming_section<'a>(settings: &Settings) -> Element<'a, Message> {
+    column![
+        text("Incoming Payloads").size(18),
+        column(
+            [
+                (Expansion::ExpandNewest, "Expand the newest payload"),
+                (Expansion::ExpandAll, "Expand every payload"),
+                (Expansion::CollapseAll, "Keep payloads collapsed"),
+            ]
+            .into_iter()
+            .map(|(expansion, label)| {
+                radio(label, expansion, Some(settings.expansion()), Message::ExpansionChanged)
+                    .text_size(14)
+                    .into()
+            })
+            .collect::<Vec<Element<Message>>>()
+        )
+        .spacing(5),
+        checkbox("Scroll to the newest payload", settings.follow_tail())
+            .on_toggle(Message::FollowTailToggled)
+            .text_size(14),
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// Creates the editor section: the command that opens a payload's origin
+fn editor_section<'a>(input: &str, current: &str) -> Element<'a, Message> {
+    let input_valid = !input.trim().is_empty();
+    let status = if !input_valid {
+        text("Enter a command, such as code --goto {file}:{line}").size(12).style(text::danger)
+    } else if input.trim() != current {
+        text("Not yet applied").size(12)
+    } else {
+        text("{file} and {line} are replaced by the payload's origin").size(12)
+    };
+
+    column![
+        text("Editor").size(18),
+        row![
+            text_input(editor::DEFAULT_EDITOR_COMMAND, input)
+                .on_input(Message::EditorCommandChanged)
+                .on_submit(Message::ApplyEditorCommand)
+                .width(Fill),
+            button(text("Apply").size(14))
+                .on_press_maybe(input_valid.then_some(Message::ApplyEditorCommand)),
+        ]
+        .spacing(5),
+        row(editor::PRESETS.iter().map(|(name, command)| {
+            button(text(*name).size(12))
+                .style(button::secondary)
+                .on_press(Message::EditorCommandChanged(command.to_string()))
+                .into()
+        }))
+        .spacing(5),
+        status,
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// Creates the JSON view section: whether collapsed paths apply to future payloads
+fn json_view_section<'a>(settings: &Settings) -> Element<'a, Message> {
+    let remembered = settings.collapsed_paths().len();
+
+    column![
+        text("JSON View").size(18),
+        checkbox("Remember collapsed paths", settings.remember_collapsed_paths())
+            .on_toggle(Message::RememberCollapsedPathsToggled)
+            .text_size(14),
+        row![
+            text(match remembered {
+                0 => "No paths remembered".to_string(),
+                1 => "1 path collapsed in every payload".to_string(),
+                count => format!("{count} paths collapsed in every payload"),
+            })
+            .size(12)
+            .width(Fill),
+            button(text("Forget").size(14))
+                .on_press_maybe((remembered > 0).then_some(Message::ForgetCollapsedPaths)),
+        ]
+        .spacing(5)
+        .align_y(iced::alignment::Vertical::Center),
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// Creates the settings modal content with server and theme selection
+pub fn settings_modal<'a>(
+    current_theme: Theme,
+    active_address: &ListenAddress,
+    server_status: &ServerStatus,
+    listen_host_input: &str,
+    listen_port_input: &str,
+    retention_inputs: &RetentionInputs,
+    editor_command_input: &str,
+    settings: &Settings,
+) -> Element<'a, Message> {
     // Find the current theme index in Theme::ALL
     let current_index = Theme::ALL
         .iter()
@@ -12,6 +189,16 @@ pub fn settings_modal<'a>(current_theme: Theme) -> Element<'a, Message> {
 
     container(
         column![
+            server_section(
+                active_address,
+                server_status,
+                listen_host_input,
+                listen_port_input,
+            ),
+            retention_section(retention_inputs, settings.retention()),
+            incoming_section(settings),
+            json_view_section(settings),
+            editor_section(editor_command_input, settings.editor_command()),
             text("Select Theme").size(18).style(move |_theme: &Theme| {
                 text::Style {
                     color: current_theme.palette().text.into(),
@@ -65,11 +252,14 @@ pub fn settings_modal<'a>(current_theme: Theme) -> Element<'a, Message> {
                     ..iced_core::Padding::default()
                 })
             )
+            // The theme list takes what the window leaves and scrolls
+            .height(Fill)
         ]
         .spacing(10),
     )
     .width(360)
-    .height(400)
+    .height(Fill)
+    .max_height(960)
     .padding(10)
     .style(|theme| container::Style {
         background: Some(theme.extended_palette().background.base.color.into()),
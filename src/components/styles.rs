@@ -1,4 +1,8 @@
+use crate::diff::Change;
+use crate::payload::Severity;
+use iced::theme::palette::Extended;
 use iced::widget::{container, svg};
+use iced::Color;
 use iced_core::Theme;
 
 pub(crate) fn svg_style_primary(theme: &Theme, _status: svg::Status) -> svg::Style {
@@ -43,3 +47,119 @@ pub(crate) fn container_code_closed(theme: &Theme) -> container::Style {
         }
     }
 }
+
+pub(crate) fn container_badge(theme: &Theme) -> container::Style {
+    let palette = theme.extended_palette();
+    let mut bg_color = palette.primary.weak.color;
+    bg_color.a = 0.3;
+
+    container::Style {
+        background: Some(bg_color.into()),
+        border: iced_core::border::rounded(3),
+        ..container::Style::default()
+    }
+}
+
+pub(crate) fn container_highlight(theme: &Theme) -> container::Style {
+    let palette = theme.extended_palette();
+    let mut bg_color = palette.warning.base.color;
+    bg_color.a = 0.35;
+
+    container::Style {
+        background: Some(bg_color.into()),
+        border: iced_core::border::rounded(2),
+        ..container::Style::default()
+    }
+}
+
+/// Parses a sender's color: a hex color such as `#f80` or `#ff8800`, or a common color name
+pub(crate) fn parse_color(color: &str) -> Option<Color> {
+    let color = color.trim().to_ascii_lowercase();
+    if color.starts_with('#') {
+        return Color::parse(&color);
+    }
+    let rgb = match color.as_str() {
+        "red" => 0xef4444,
+        "orange" => 0xf97316,
+        "yellow" => 0xeab308,
+        "green" => 0x22c55e,
+        "teal" => 0x14b8a6,
+        "blue" => 0x3b82f6,
+        "purple" => 0xa855f7,
+        "pink" => 0xec4899,
+        "gray" | "grey" => 0x6b7280,
+        _ => return None,
+    };
+    Some(Color::from_rgb8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+/// Badge tinted with the sender's color, or the primary color without one
+pub(crate) fn container_label(color: Option<Color>) -> impl Fn(&Theme) -> container::Style {
+    move |theme: &Theme| {
+        let mut bg_color = color.unwrap_or(theme.extended_palette().primary.base.color);
+        bg_color.a = 0.45;
+
+        container::Style {
+            background: Some(bg_color.into()),
+            border: iced_core::border::rounded(3),
+            ..container::Style::default()
+        }
+    }
+}
+
+/// Badge tinted by how serious the sender marked a payload
+pub(crate) fn container_severity(severity: Severity) -> impl Fn(&Theme) -> container::Style {
+    move |theme: &Theme| {
+        let palette = theme.extended_palette();
+        let mut bg_color = match severity {
+            Severity::Info => palette.primary.base.color,
+            Severity::Warn => palette.warning.base.color,
+            Severity::Error => palette.danger.base.color,
+        };
+        bg_color.a = 0.6;
+
+        container::Style {
+            background: Some(bg_color.into()),
+            border: iced_core::border::rounded(3),
+            ..container::Style::default()
+        }
+    }
+}
+
+/// Background for the JSON line selected for copying
+pub(crate) fn container_selected_line(theme: &Theme) -> container::Style {
+    let palette = theme.extended_palette();
+    let mut bg_color = palette.primary.weak.color;
+    bg_color.a = 0.2;
+
+    container::Style {
+        background: Some(bg_color.into()),
+        border: iced_core::border::rounded(2),
+        ..container::Style::default()
+    }
+}
+
+/// Background for a diff line, tinted by how it changed
+pub(crate) fn container_diff(change: &Change) -> impl Fn(&Theme) -> container::Style {
+    let tint: Option<fn(&Extended) -> Color> = match change {
+        Change::Same => None,
+        Change::Added => Some(|palette| palette.success.base.color),
+        Change::Removed => Some(|palette| palette.danger.base.color),
+        Change::Changed(_) => Some(|palette| palette.warning.base.color),
+        Change::Moved(_) => Some(|palette| palette.primary.base.color),
+    };
+
+    move |theme: &Theme| {
+        let Some(tint) = tint else {
+            return container::Style::default();
+        };
+        let mut bg_color = tint(theme.extended_palette());
+        bg_color.a = 0.15;
+
+        container::Style {
+            background: Some(bg_color.into()),
+            border: iced_core::border::rounded(2),
+            ..container::Style::default()
+        }
+    }
+}
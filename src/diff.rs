@@ -0,0 +1,407 @@
+use serde_json::Value;
+
+// Arrays whose LCS table would exceed this many cells are compared index by index
+const MAX_LCS_CELLS: usize = 1_000_000;
+
+/// How a diff line differs between the old and the new payload
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Same,
+    Added,
+    Removed,
+    /// A scalar that changed value or type, holding the old value as rendered
+    Changed(String),
+    /// An array element found at another index, holding its old index
+    Moved(usize),
+}
+
+/// One rendered line of a structural diff
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub depth: usize,
+    /// Object key, or `None` for array elements and closing brackets
+    pub key: Option<String>,
+    pub text: String,
+    pub change: Change,
+}
+
+/// Structural diff of two JSON values, as lines ready to render
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    pub lines: Vec<DiffLine>,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub moved: usize,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added + self.removed + self.changed + self.moved == 0
+    }
+
+    fn push(&mut self, depth: usize, key: Option<String>, text: String, change: Change) {
+        self.lines.push(DiffLine {
+            depth,
+            key,
+            text,
+            change,
+        });
+    }
+
+    /// Pushes a whole value with a single change, expanding containers line by line
+    fn push_whole(&mut self, value: &Value, depth: usize, key: Option<String>, change: &Change) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                self.push(depth, key, "{".to_string(), change.clone());
+                for (child_key, child) in map {
+                    self.push_whole(child, depth + 1, Some(child_key.clone()), change);
+                }
+                self.push(depth, None, "}".to_string(), change.clone());
+            }
+            Value::Array(items) if !items.is_empty() => {
+                self.push(depth, key, "[".to_string(), change.clone());
+                for item in items {
+                    self.push_whole(item, depth + 1, None, change);
+                }
+                self.push(depth, None, "]".to_string(), change.clone());
+            }
+            scalar => self.push(depth, key, scalar.to_string(), change.clone()),
+        }
+    }
+
+    fn added(&mut self, value: &Value, depth: usize, key: Option<String>) {
+        self.added += 1;
+        self.push_whole(value, depth, key, &Change::Added);
+    }
+
+    fn removed(&mut self, value: &Value, depth: usize, key: Option<String>) {
+        self.removed += 1;
+        self.push_whole(value, depth, key, &Change::Removed);
+    }
+
+    fn value(&mut self, old: &Value, new: &Value, depth: usize, key: Option<String>) {
+        if old == new {
+            self.push(depth, key, summary(new), Change::Same);
+            return;
+        }
+
+        match (old, new) {
+            (Value::Object(old_map), Value::Object(new_map)) => {
+                self.push(depth, key, "{".to_string(), Change::Same);
+                for (child_key, old_child) in old_map {
+                    match new_map.get(child_key) {
+                        Some(new_child) => {
+                            self.value(old_child, new_child, depth + 1, Some(child_key.clone()));
+                        }
+                        None => self.removed(old_child, depth + 1, Some(child_key.clone())),
+                    }
+                }
+                for (child_key, new_child) in new_map {
+                    if !old_map.contains_key(child_key) {
+                        self.added(new_child, depth + 1, Some(child_key.clone()));
+                    }
+                }
+                self.push(depth, None, "}".to_string(), Change::Same);
+            }
+            (Value::Array(old_items), Value::Array(new_items)) => {
+                self.push(depth, key, "[".to_string(), Change::Same);
+                self.array(old_items, new_items, depth + 1);
+                self.push(depth, None, "]".to_string(), Change::Same);
+            }
+            (Value::Object(_) | Value::Array(_), _) | (_, Value::Object(_) | Value::Array(_)) => {
+                // A container replaced by something else reads best as a removal and an addition
+                self.removed(old, depth, key.clone());
+                self.added(new, depth, key);
+            }
+            (old, new) => {
+                self.changed += 1;
+                self.push(depth, key, new.to_string(), Change::Changed(old.to_string()));
+            }
+        }
+    }
+
+    fn array(&mut self, old: &[Value], new: &[Value], depth: usize) {
+        let mut old_matched = vec![false; old.len()];
+        let mut new_ops: Vec<Option<ArrayOp>> = vec![None; new.len()];
+
+        // Elements kept in order
+        for (i, j) in longest_common_subsequence(old, new) {
+            old_matched[i] = true;
+            new_ops[j] = Some(ArrayOp::Same(i));
+        }
+
+        // Equal elements found elsewhere were moved
+        for (j, item) in new.iter().enumerate() {
+            if new_ops[j].is_some() {
+                continue;
+            }
+            if let Some(i) = (0..old.len()).find(|&i| !old_matched[i] && &old[i] == item) {
+                old_matched[i] = true;
+                new_ops[j] = Some(ArrayOp::Moved(i));
+            }
+        }
+
+        // Whatever is left is paired up in order and diffed, the rest was added or removed
+        let mut unmatched_old = (0..old.len())
+            .filter(|&i| !old_matched[i])
+            .collect::<Vec<_>>()
+            .into_iter();
+        for op in new_ops.iter_mut().filter(|op| op.is_none()) {
+            if let Some(i) = unmatched_old.next() {
+                old_matched[i] = true;
+                *op = Some(ArrayOp::Paired(i));
+            }
+        }
+        let mut removed = (0..old.len()).filter(|&i| !old_matched[i]).peekable();
+
+        for (j, op) in new_ops.into_iter().enumerate() {
+            // Removed elements are shown before the first element that followed them
+            if let Some(ArrayOp::Same(i) | ArrayOp::Paired(i)) = op {
+                while let Some(r) = removed.next_if(|&r| r < i) {
+                    self.removed(&old[r], depth, None);
+                }
+            }
+
+            match op {
+                Some(ArrayOp::Same(i)) => self.push(depth, None, summary(&old[i]), Change::Same),
+                Some(ArrayOp::Paired(i)) => self.value(&old[i], &new[j], depth, None),
+                Some(ArrayOp::Moved(i)) => {
+                    self.moved += 1;
+                    self.push(depth, None, summary(&new[j]), Change::Moved(i));
+                }
+                None => self.added(&new[j], depth, None),
+            }
+        }
+        for r in removed {
+            self.removed(&old[r], depth, None);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArrayOp {
+    Same(usize),
+    Moved(usize),
+    Paired(usize),
+}
+
+/// Single-line rendering of an unchanged value; containers are summarized
+fn summary(value: &Value) -> String {
+    match value {
+        Value::Object(map) if !map.is_empty() => format!("{{ {} keys }}", map.len()),
+        Value::Array(items) if !items.is_empty() => format!("[ {} items ]", items.len()),
+        value => value.to_string(),
+    }
+}
+
+/// Index pairs of the longest run of equal elements kept in order
+fn longest_common_subsequence(old: &[Value], new: &[Value]) -> Vec<(usize, usize)> {
+    if old.len().saturating_mul(new.len()) > MAX_LCS_CELLS {
+        return (0..old.len().min(new.len()))
+            .filter(|&i| old[i] == new[i])
+            .map(|i| (i, i))
+            .collect();
+    }
+
+    // lengths[i][j] is the LCS length of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Computes the structural diff turning `old` into `new`
+pub fn diff(old: &Value, new: &Value) -> Diff {
+    let mut diff = Diff::default();
+    diff.value(old, new, 0, None);
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// The diff lines as (depth, key, text, change), for compact comparisons
+    fn lines(diff: &Diff) -> Vec<(usize, Option<&str>, &str, Change)> {
+        diff.lines
+            .iter()
+            .map(|line| {
+                (
+                    line.depth,
+                    line.key.as_deref(),
+                    line.text.as_str(),
+                    line.change.clone(),
+                )
+            })
+            .collect()
+    }
+
+    fn counts(diff: &Diff) -> [usize; 4] {
+        [diff.added, diff.removed, diff.changed, diff.moved]
+    }
+
+    #[test]
+    fn identical_values_are_one_unchanged_line() {
+        let value = json!({ "a": [1, 2], "b": { "c": null } });
+        let diff = diff(&value, &value);
+        assert!(diff.is_empty());
+        assert_eq!(lines(&diff), [(0, None, "{ 2 keys }", Change::Same)]);
+    }
+
+    #[test]
+    fn object_keys_are_added_removed_and_changed() {
+        let old = json!({ "a": 1, "b": 2, "c": { "x": true } });
+        let new = json!({ "a": 1, "b": "2", "d": [1] });
+        let diff = diff(&old, &new);
+
+        assert_eq!(counts(&diff), [1, 1, 1, 0]);
+        assert_eq!(
+            lines(&diff),
+            [
+                (0, None, "{", Change::Same),
+                (1, Some("a"), "1", Change::Same),
+                (1, Some("b"), "\"2\"", Change::Changed("2".to_string())),
+                (1, Some("c"), "{", Change::Removed),
+                (2, Some("x"), "true", Change::Removed),
+                (1, None, "}", Change::Removed),
+                (1, Some("d"), "[", Change::Added),
+                (2, None, "1", Change::Added),
+                (1, None, "]", Change::Added),
+                (0, None, "}", Change::Same),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_objects_are_diffed_key_by_key() {
+        let diff = diff(
+            &json!({ "a": { "b": 1, "c": 2 } }),
+            &json!({ "a": { "b": 1, "c": 3 } }),
+        );
+        assert_eq!(counts(&diff), [0, 0, 1, 0]);
+        assert_eq!(
+            lines(&diff),
+            [
+                (0, None, "{", Change::Same),
+                (1, Some("a"), "{", Change::Same),
+                (2, Some("b"), "1", Change::Same),
+                (2, Some("c"), "3", Change::Changed("2".to_string())),
+                (1, None, "}", Change::Same),
+                (0, None, "}", Change::Same),
+            ]
+        );
+    }
+
+    #[test]
+    fn container_replaced_by_a_scalar_is_removed_and_added() {
+        let diff = diff(&json!({ "a": [1] }), &json!({ "a": 1 }));
+        assert_eq!(counts(&diff), [1, 1, 0, 0]);
+        assert_eq!(
+            lines(&diff)[1..5],
+            [
+                (1, Some("a"), "[", Change::Removed),
+                (2, None, "1", Change::Removed),
+                (1, None, "]", Change::Removed),
+                (1, Some("a"), "1", Change::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn array_elements_are_added_and_removed_in_place() {
+        let diff = diff(&json!([1, 2]), &json!([1, 2, 3]));
+        assert_eq!(counts(&diff), [1, 0, 0, 0]);
+        assert_eq!(
+            lines(&diff),
+            [
+                (0, None, "[", Change::Same),
+                (1, None, "1", Change::Same),
+                (1, None, "2", Change::Same),
+                (1, None, "3", Change::Added),
+                (0, None, "]", Change::Same),
+            ]
+        );
+
+        let diff = diff(&json!([1, 2, 3]), &json!([1, 3]));
+        assert_eq!(counts(&diff), [0, 1, 0, 0]);
+        assert_eq!(
+            lines(&diff),
+            [
+                (0, None, "[", Change::Same),
+                (1, None, "1", Change::Same),
+                (1, None, "2", Change::Removed),
+                (1, None, "3", Change::Same),
+                (0, None, "]", Change::Same),
+            ]
+        );
+    }
+
+    #[test]
+    fn array_elements_found_elsewhere_are_moved() {
+        let diff = diff(&json!(["a", "b", "c"]), &json!(["c", "a", "b"]));
+        assert_eq!(counts(&diff), [0, 0, 0, 1]);
+        assert_eq!(
+            lines(&diff),
+            [
+                (0, None, "[", Change::Same),
+                (1, None, "\"c\"", Change::Moved(2)),
+                (1, None, "\"a\"", Change::Same),
+                (1, None, "\"b\"", Change::Same),
+                (0, None, "]", Change::Same),
+            ]
+        );
+    }
+
+    #[test]
+    fn unmatched_array_elements_are_paired_and_diffed() {
+        let diff = diff(
+            &json!([{ "id": 1, "v": 1 }, 7]),
+            &json!([{ "id": 1, "v": 2 }, 7]),
+        );
+        assert_eq!(counts(&diff), [0, 0, 1, 0]);
+        assert_eq!(
+            lines(&diff),
+            [
+                (0, None, "[", Change::Same),
+                (1, None, "{", Change::Same),
+                (2, Some("id"), "1", Change::Same),
+                (2, Some("v"), "2", Change::Changed("1".to_string())),
+                (1, None, "}", Change::Same),
+                (1, None, "7", Change::Same),
+                (0, None, "]", Change::Same),
+            ]
+        );
+    }
+
+    #[test]
+    fn longest_common_subsequence_keeps_elements_in_order() {
+        let old = [json!(1), json!(2), json!(3), json!(4)];
+        let new = [json!(2), json!(4), json!(1)];
+        assert_eq!(longest_common_subsequence(&old, &new), [(1, 0), (3, 1)]);
+        assert!(longest_common_subsequence(&old, &[]).is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::payload::Origin;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Command used to open a payload's origin when none has been configured
+pub const DEFAULT_EDITOR_COMMAND: &str = "code --goto {file}:{line}";
+
+/// Editor commands offered in the settings, by name
+pub const PRESETS: [(&str, &str); 4] = [
+    ("VS Code", DEFAULT_EDITOR_COMMAND),
+    ("JetBrains", "idea --line {line} {file}"),
+    ("Sublime", "subl {file}:{line}"),
+    ("Vim", "gvim +{line} {file}"),
+];
+
+/// Splits a command template into arguments on whitespace, keeping quoted runs together
+fn split_args(template: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut in_arg = false;
+
+    for c in template.chars() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// Opens `origin` with the editor command `template`, in which `{file}` and `{line}`
+/// are replaced by the location. A missing line opens the first one.
+///
+/// The file comes from whoever sent the payload, so it must be an existing local file,
+/// and may not start with `-` or `+`, which editors read as options or commands
+/// (`gvim +!cmd` runs a shell command).
+pub fn open(template: &str, origin: &Origin) -> io::Result<()> {
+    if origin.file.starts_with(['-', '+']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Refusing to open '{}', which the editor would read as an option", origin.file),
+        ));
+    }
+    if !Path::new(&origin.file).is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("'{}' is not a file on this machine", origin.file),
+        ));
+    }
+
+    let line = origin.line.unwrap_or(1).to_string();
+    // Substituted after splitting, so file paths with spaces stay a single argument
+    let mut args = split_args(template)
+        .into_iter()
+        .map(|arg| arg.replace("{file}", &origin.file).replace("{line}", &line));
+
+    let program = args
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The editor command is empty"))?;
+    let mut child = Command::new(&program)
+        .args(args)
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("Could not run '{program}': {e}")))?;
+
+    // Reap the editor once it exits, without blocking the caller
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(file: &str) -> Origin {
+        Origin {
+            file: file.to_string(),
+            line: Some(12),
+        }
+    }
+
+    #[test]
+    fn split_args_keeps_quoted_runs_together() {
+        assert_eq!(
+            split_args("code --goto {file}:{line}"),
+            ["code", "--goto", "{file}:{line}"]
+        );
+        assert_eq!(
+            split_args(r#"  "C:\Program Files\Editor\edit.exe"   '{file}' -l{line} "#),
+            [r"C:\Program Files\Editor\edit.exe", "{file}", "-l{line}"]
+        );
+        assert_eq!(split_args(r#"vim "" x"#), ["vim", "", "x"]);
+        assert_eq!(split_args(r#"say "it's here""#), ["say", "it's here"]);
+        assert!(split_args("   ").is_empty());
+    }
+
+    #[test]
+    fn open_refuses_files_that_look_like_options() {
+        for file in ["-c", "--help", "+!rm -rf ~", "+12"] {
+            let error = open("gvim +{line} {file}", &origin(file)).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{file}");
+        }
+    }
+
+    #[test]
+    fn open_refuses_files_not_on_this_machine() {
+        let missing = std::env::temp_dir().join("dbug-editor-test-missing.php");
+        let error = open(
+            DEFAULT_EDITOR_COMMAND,
+            &origin(&missing.display().to_string()),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+        let directory = std::env::temp_dir().display().to_string();
+        let error = open(DEFAULT_EDITOR_COMMAND, &origin(&directory)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn open_reports_an_empty_command() {
+        let file = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let error = open("  ", &origin(file)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::payload::{Annotations, Origin, Severity};
+use serde_json::{Map, Value};
+
+/// Top-level key of a JSON object payload holding its annotations
+pub const ENVELOPE_KEY: &str = "__dbug";
+
+/// Prefix of the request headers holding annotations for every payload in the body
+const HEADER_PREFIX: &str = "x-dbug-";
+
+fn string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.trim().to_string()).filter(|text| !text.is_empty()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// Tags given as an array of strings or as one comma-separated string
+fn tags(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(items) => items.iter().filter_map(string).collect(),
+        Value::String(text) => text
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The origin as `"file:line"`, as `{"file", "line"}`, or as separate `file` and `line` keys
+fn origin(envelope: &Map<String, Value>) -> Option<Origin> {
+    let from_parts = |parts: &Map<String, Value>| {
+        let file = parts.get("file").and_then(string)?;
+        let line = parts.get("line").and_then(|line| match line {
+            Value::Number(number) => number.as_u64().and_then(|line| u32::try_from(line).ok()),
+            Value::String(text) => text.trim().parse().ok(),
+            _ => None,
+        });
+        Some(Origin { file, line })
+    };
+
+    match envelope.get("origin") {
+        Some(Value::String(location)) => Origin::parse(location),
+        Some(Value::Object(parts)) => from_parts(parts),
+        _ => from_parts(envelope),
+    }
+}
+
+/// Annotations from an envelope object; unknown keys are ignored
+fn from_object(envelope: &Map<String, Value>) -> Annotations {
+    Annotations {
+        label: envelope.get("label").and_then(string),
+        color: envelope.get("color").and_then(string),
+        severity: envelope
+            .get("severity")
+            .and_then(Value::as_str)
+            .and_then(Severity::parse),
+        origin: origin(envelope),
+        tags: envelope.get("tags").map(tags).unwrap_or_default(),
+    }
+}
+
+/// Annotations from `X-Dbug-Label`, `X-Dbug-Color`, `X-Dbug-Severity`,
+/// `X-Dbug-Origin` (`file:line`) and `X-Dbug-Tags` (comma-separated)
+fn from_headers(headers: &[(String, String)]) -> Annotations {
+    let mut envelope = Map::new();
+    for (name, value) in headers {
+        let name = name.to_ascii_lowercase();
+        if let Some(key) = name.strip_prefix(HEADER_PREFIX) {
+            envelope.insert(key.to_string(), Value::String(value.clone()));
+        }
+    }
+    from_object(&envelope)
+}
+
+/// Removes the `__dbug` envelope from a JSON object payload and returns its
+/// annotations, falling back to the `X-Dbug-*` request headers for anything
+/// the envelope leaves out. Other payloads are left untouched.
+pub fn extract(value: &mut Value, headers: &[(String, String)]) -> Annotations {
+    let body = match value {
+        Value::Object(object) => match object.remove(ENVELOPE_KEY) {
+            Some(Value::Object(envelope)) => from_object(&envelope),
+            Some(other) => {
+                // Not an envelope after all, so keep it as part of the payload
+                object.insert(ENVELOPE_KEY.to_string(), other);
+                Annotations::default()
+            }
+            None => Annotations::default(),
+        },
+        _ => Annotations::default(),
+    };
+    let headers = from_headers(headers);
+
+    Annotations {
+        label: body.label.or(headers.label),
+        color: body.color.or(headers.color),
+        severity: body.severity.or(headers.severity),
+        origin: body.origin.or(headers.origin),
+        tags: if body.tags.is_empty() { headers.tags } else { body.tags },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::styles::parse_color;
+    use serde_json::json;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn origin(file: &str, line: Option<u32>) -> Option<Origin> {
+        Some(Origin {
+            file: file.to_string(),
+            line,
+        })
+    }
+
+    #[test]
+    fn envelope_is_removed_and_read() {
+        let mut value = json!({
+            "__dbug": {
+                "label": " Checkout ",
+                "color": "#ff8800",
+                "severity": "WARNING",
+                "origin": "src/cart.php:12",
+                "tags": ["cart", " ", "slow"],
+                "unknown": true
+            },
+            "total": 3
+        });
+        let annotations = extract(&mut value, &[]);
+
+        assert_eq!(value, json!({ "total": 3 }));
+        assert_eq!(
+            annotations,
+            Annotations {
+                label: Some("Checkout".to_string()),
+                color: Some("#ff8800".to_string()),
+                severity: Some(Severity::Warn),
+                origin: origin("src/cart.php", Some(12)),
+                tags: vec!["cart".to_string(), "slow".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn origin_may_be_an_object_or_separate_keys() {
+        let mut value = json!({ "__dbug": { "origin": { "file": "a.rs", "line": "7" } } });
+        assert_eq!(extract(&mut value, &[]).origin, origin("a.rs", Some(7)));
+
+        let mut value = json!({ "__dbug": { "file": "b.rs", "line": 9, "label": 42 } });
+        let annotations = extract(&mut value, &[]);
+        assert_eq!(annotations.origin, origin("b.rs", Some(9)));
+        assert_eq!(annotations.label.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn plain_bodies_are_left_untouched() {
+        for body in [
+            json!({ "a": 1 }),
+            json!({ "__dbug": "not an envelope", "a": 1 }),
+            json!([{ "__dbug": { "label": "nested" } }]),
+            json!("text"),
+        ] {
+            let mut value = body.clone();
+            assert_eq!(extract(&mut value, &[]), Annotations::default());
+            assert_eq!(value, body);
+        }
+    }
+
+    #[test]
+    fn headers_annotate_bodies_without_an_envelope() {
+        let mut value = json!({ "a": 1 });
+        let annotations = extract(
+            &mut value,
+            &headers(&[
+                ("X-Dbug-Label", "Import"),
+                ("x-dbug-severity", "fatal"),
+                ("X-DBUG-TAGS", "a, b,,c"),
+                ("X-Dbug-Origin", r"C:\app.php:12"),
+                ("Authorization", "Bearer secret"),
+            ]),
+        );
+
+        assert_eq!(value, json!({ "a": 1 }));
+        assert_eq!(
+            annotations,
+            Annotations {
+                label: Some("Import".to_string()),
+                color: None,
+                severity: Some(Severity::Error),
+                origin: origin(r"C:\app.php", Some(12)),
+                tags: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn envelope_wins_over_headers_which_fill_the_gaps() {
+        let mut value = json!({ "__dbug": { "label": "body", "tags": [] } });
+        let annotations = extract(
+            &mut value,
+            &headers(&[
+                ("X-Dbug-Label", "header"),
+                ("X-Dbug-Color", "red"),
+                ("X-Dbug-Tags", "h"),
+            ]),
+        );
+        assert_eq!(annotations.label.as_deref(), Some("body"));
+        assert_eq!(annotations.color.as_deref(), Some("red"));
+        assert_eq!(annotations.tags, ["h"]);
+    }
+
+    #[test]
+    fn severity_names_are_parsed_loosely() {
+        assert_eq!(Severity::parse(" Debug "), Some(Severity::Info));
+        assert_eq!(Severity::parse("warn"), Some(Severity::Warn));
+        assert_eq!(Severity::parse("CRITICAL"), Some(Severity::Error));
+        assert_eq!(Severity::parse("loud"), None);
+
+        let mut value = json!({ "__dbug": { "severity": "loud" } });
+        assert_eq!(extract(&mut value, &[]).severity, None);
+    }
+
+    #[test]
+    fn only_known_names_and_hex_colors_are_shown() {
+        assert!(parse_color("#ff8800").is_some());
+        assert!(parse_color(" RED ").is_some());
+        assert!(parse_color("grey").is_some());
+        assert!(parse_color("chartreuse").is_none());
+        assert!(parse_color("#zzzzzz").is_none());
+        assert!(parse_color("").is_none());
+    }
+}
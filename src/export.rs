@@ -0,0 +1,283 @@
+use crate::payload::{Payload, PayloadKind};
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// File formats payloads can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Pretty printed array of stored payload records
+    Json,
+    /// One stored payload record per line
+    Ndjson,
+    /// HTTP Archive of the requests that carried the payloads
+    Har,
+    /// Human readable report with a code block per payload
+    Markdown,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 4] = [
+        ExportFormat::Json,
+        ExportFormat::Ndjson,
+        ExportFormat::Har,
+        ExportFormat::Markdown,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(ExportFormat::Json),
+            "ndjson" | "jsonl" => Some(ExportFormat::Ndjson),
+            "har" => Some(ExportFormat::Har),
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Har => "har",
+            ExportFormat::Markdown => "md",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExportFormat::Json => "JSON",
+            ExportFormat::Ndjson => "NDJSON",
+            ExportFormat::Har => "HAR",
+            ExportFormat::Markdown => "Markdown",
+        })
+    }
+}
+
+/// The payload body as it was sent, as closely as the stored value allows
+fn body_text(payload: &Payload) -> String {
+    match (payload.kind, &payload.value) {
+        (PayloadKind::Text | PayloadKind::Binary, Value::String(body)) => body.clone(),
+        (PayloadKind::Form, Value::Object(fields)) => {
+            let mut serializer = form_urlencoded::Serializer::new(String::new());
+            for (name, value) in fields {
+                let values = match value {
+                    Value::Array(values) => values.iter().collect(),
+                    value => vec![value],
+                };
+                for value in values {
+                    match value {
+                        Value::String(value) => serializer.append_pair(name, value),
+                        value => serializer.append_pair(name, &value.to_string()),
+                    };
+                }
+            }
+            serializer.finish()
+        }
+        (_, value) => value.to_string(),
+    }
+}
+
+fn har_entry(payload: &Payload) -> Option<Value> {
+    let request = payload.request.as_ref()?;
+    let host = request
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+        .map_or("localhost", |(_, value)| value.as_str());
+    let query = request.query.as_deref().filter(|query| !query.is_empty());
+    let url = match query {
+        Some(query) => format!("http://{host}{}?{query}", request.path),
+        None => format!("http://{host}{}", request.path),
+    };
+    let query_string = query
+        .map(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let headers = request
+        .headers
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect::<Vec<_>>();
+    let mime_type = request
+        .content_type
+        .clone()
+        .unwrap_or_else(|| match payload.kind {
+            PayloadKind::Json => "application/json".to_string(),
+            PayloadKind::Text => "text/plain".to_string(),
+            PayloadKind::Form => "application/x-www-form-urlencoded".to_string(),
+            PayloadKind::Binary => "application/octet-stream".to_string(),
+        });
+
+    let text = body_text(payload);
+    let body_size = text.len();
+    let mut post_data = json!({ "mimeType": mime_type, "text": text });
+    if payload.kind == PayloadKind::Binary {
+        post_data["comment"] = json!("Body is hex encoded");
+    }
+
+    // What the server replied when it stored the payload, short of the eviction count
+    let reply = json!({
+        "id": payload.id,
+        "channel": payload.channel,
+        "kind": payload.kind,
+        "size": payload.size,
+    })
+    .to_string();
+
+    Some(json!({
+        "startedDateTime": payload.received_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "time": 0,
+        "request": {
+            "method": request.method,
+            "url": url,
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": headers,
+            "queryString": query_string,
+            "postData": post_data,
+            "headersSize": -1,
+            "bodySize": body_size,
+        },
+        "response": {
+            "status": 200,
+            "statusText": "OK",
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": [],
+            "content": { "size": reply.len(), "mimeType": "application/json", "text": reply },
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": reply.len(),
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": 0, "receive": 0 },
+        "comment": format!("dbug payload {} in channel {}", payload.id, payload.channel),
+    }))
+}
+
+fn markdown(payloads: &[&Payload]) -> String {
+    let mut report = format!(
+        "# dbug export\n\n{} payloads, exported {}\n",
+        payloads.len(),
+        Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    );
+
+    for payload in payloads {
+        report.push_str(&format!(
+            "\n## {} · {}\n\n",
+            payload.received_at.format("%Y-%m-%d %H:%M:%S%.3f UTC"),
+            payload.channel
+        ));
+        if let Some(request) = &payload.request {
+            report.push_str(&format!("`{}`\n\n", request.summary()));
+        }
+
+        let (language, body) = match (payload.kind, &payload.value) {
+            (PayloadKind::Text, Value::String(body)) => ("text", body.clone()),
+            (PayloadKind::Binary, Value::String(hex)) => ("hex", hex.clone()),
+            (_, value) => (
+                "json",
+                serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string()),
+            ),
+        };
+        // Longer fences than any backtick run in the body keep the block intact
+        let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+        let fence = "`".repeat(longest_run.max(2) + 1);
+        report.push_str(&format!("{fence}{language}\n{body}\n{fence}\n"));
+    }
+
+    report
+}
+
+/// Renders payloads in the given format. HAR archives only include payloads
+/// that still have their request metadata.
+pub fn export(payloads: &[&Payload], format: ExportFormat) -> serde_json::Result<String> {
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(payloads),
+        ExportFormat::Ndjson => payloads
+            .iter()
+            .map(|payload| serde_json::to_string(payload).map(|line| line + "\n"))
+            .collect(),
+        ExportFormat::Har => {
+            let entries = payloads
+                .iter()
+                .filter_map(|payload| har_entry(payload))
+                .collect::<Vec<_>>();
+            serde_json::to_string_pretty(&json!({
+                "log": {
+                    "version": "1.2",
+                    "creator": { "name": "dbug-desktop", "version": env!("CARGO_PKG_VERSION") },
+                    "entries": entries,
+                }
+            }))
+        }
+        ExportFormat::Markdown => Ok(markdown(payloads)),
+    }
+}
+
+/// Where the GUI writes exports: a timestamped file in the downloads directory, or home.
+/// A `-N` suffix keeps exports made within the same millisecond apart
+pub fn default_path(format: ExportFormat) -> PathBuf {
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."));
+    unused_path(&dir, &Utc::now().format("%Y%m%d-%H%M%S-%3f").to_string(), format)
+}
+
+fn unused_path(dir: &Path, stamp: &str, format: ExportFormat) -> PathBuf {
+    let extension = format.extension();
+    let mut path = dir.join(format!("dbug-export-{stamp}.{extension}"));
+    let mut suffix = 1;
+    while path.exists() {
+        suffix += 1;
+        path = dir.join(format!("dbug-export-{stamp}-{suffix}.{extension}"));
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::RequestMeta;
+    use std::fs;
+
+    #[test]
+    fn har_body_size_is_the_length_of_the_emitted_text() {
+        let mut payload = Payload::from_legacy("1".to_string(), json!("00ff10"), 3);
+        payload.kind = PayloadKind::Binary;
+        payload.request = Some(RequestMeta {
+            method: "POST".to_string(),
+            path: "/".to_string(),
+            ..RequestMeta::default()
+        });
+
+        let entry = har_entry(&payload).unwrap();
+        assert_eq!(entry["request"]["postData"]["text"], "00ff10");
+        assert_eq!(entry["request"]["bodySize"], 6);
+    }
+
+    #[test]
+    fn default_paths_do_not_overwrite_existing_exports() {
+        let dir = std::env::temp_dir().join(format!("dbug-export-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = unused_path(&dir, "stamp", ExportFormat::Json);
+        assert_eq!(first, dir.join("dbug-export-stamp.json"));
+        fs::write(&first, "").unwrap();
+        let second = unused_path(&dir, "stamp", ExportFormat::Json);
+        assert_eq!(second, dir.join("dbug-export-stamp-2.json"));
+        fs::write(&second, "").unwrap();
+        assert_eq!(
+            unused_path(&dir, "stamp", ExportFormat::Json),
+            dir.join("dbug-export-stamp-3.json")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,20 +4,41 @@ use iced::widget::scrollable::AbsoluteOffset;
 use iced::{keyboard, window, Length, Theme};
 
 use crate::components;
+use crate::components::json_highlight::JsonViewState;
 use crate::components::styles;
-use crate::app::{App, Message};
+use crate::app::{parse_remembered_paths, App, Message};
 use crate::app::Message::Server;
+use crate::cli::Args;
+use crate::clipboard::{self, CopyTarget};
+use crate::diff::{self, Diff};
+use crate::editor;
+use crate::export;
+use crate::import;
+use crate::payload::{Payload, PayloadKind};
+use crate::search::{self, SearchQuery, Segment};
 use crate::server;
-use crate::server::ServerMessage;
-use crate::settings::Settings;
+use crate::server::{ListenAddress, ServerConfig, ServerMessage, ServerStatus};
+use crate::settings::{Expansion, Settings};
+use crate::storage::{Added, ImportSummary, Storage};
 use iced::widget::{self, button, column, container, horizontal_space, row, svg, text};
 use iced::{Bottom, Element, Fill, Font, Subscription, Task};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
 
 /// Initializes and runs the GUI application
-pub fn gui() -> iced::Result {
+pub fn gui(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     let settings = Settings::load();
+    // Opened before the window, so a store in use by another process is reported plainly
+    let storage = Storage::new(settings.retention().clone())?;
 
-    iced::application(App::default, App::update, App::view)
+    let new_app = move || {
+        let mut app = App::new(args.clone(), storage.clone());
+        app.update_view_caches();
+        app
+    };
+
+    iced::application(new_app, App::update, App::view)
         .title("dbug desktop")
         .subscription(App::subscription)
         .font(include_bytes!("../assets/fonts/firacode.ttf").as_slice())
@@ -28,44 +49,87 @@ pub fn gui() -> iced::Result {
             position: window::Position::Specific(settings.get_window_position()),
             ..window::Settings::default()
         })
-        .run()
+        .run()?;
+    Ok(())
 }
 
 impl App {
     fn subscription(&self) -> Subscription<Message> {
         Subscription::batch(vec![
-            Subscription::run(server::listen).map(Server),
-            iced::event::listen_with(|event, _status, _window_id| match event {
+            Subscription::run_with(
+                ServerConfig {
+                    address: self.listen_address.clone(),
+                    storage: self.storage.clone(),
+                    origins: self.allowed_origins.clone(),
+                },
+                server::listen,
+            )
+            .map(Server),
+            iced::event::listen_with(|event, status, _window_id| match event {
                 Event::Window(window::Event::Closed) => Some(Message::WindowClosed),
                 Event::Window(window::Event::Moved(position)) => {
                     Some(Message::WindowMoved(position))
                 }
                 Event::Window(window::Event::Resized(size)) => Some(Message::WindowResized(size)),
+                Event::Window(window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+                // Keys a focused widget already handled, such as copying from a text input, are skipped
+                Event::Keyboard(_) if status == iced::event::Status::Ignored => Some(Message::Event(event)),
                 _ => None,
             }),
         ])
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
+        let task = self.handle(message);
+        self.update_view_caches();
+        task
+    }
+
+    /// Brings the caches `view` reads up to date, unless nothing they depend on changed;
+    /// most messages, such as scrolling, moving the window or typing, change none of it
+    fn update_view_caches(&mut self) {
+        if std::mem::take(&mut self.view_caches_stale) {
+            self.update_highlight_cache();
+            self.update_collapsed_nodes();
+        }
+    }
+
+    /// Tokenizes the expanded payloads' JSON once, so renders and scrolling only lay out lines
+    fn update_highlight_cache(&mut self) {
+        self.highlight_cache.sync(
+            self.payload_list_cache
+                .iter()
+                .filter(|payload| payload.kind == PayloadKind::Json && self.expanded_payload_ids.contains(&payload.id))
+                .map(|payload| (payload.id.as_str(), &payload.value)),
+        );
+    }
+
+    fn handle(&mut self, message: Message) -> Task<Message> {
         match message {
             Server(server_message) => {
                 match server_message {
-                    ServerMessage::PayloadReceived(value) => {
-                        let scroll_command;
-                        if let Err(e) = self.storage.add_json(&value) {
-                            eprintln!("Failed to store payload: {e}");
-                            scroll_command = Task::none();
+                    // The server has already stored the payload
+                    ServerMessage::PayloadReceived(added) => {
+                        self.evicted_count += added.evicted.len();
+                        self.cache_added(&added);
+                        if self.paused {
+                            self.held_payload_ids.insert(added.payload.id);
+                            Task::none()
                         } else {
-                            self.payload_list_cache = self.storage.get_all();
-                            self.expanded_payload_id = self.payload_list_cache.first().map(|(id, _)| id.clone());
-                            self.collapsed_json_lines.clear();
-
-                            scroll_command = widget::scrollable::scroll_to::<Message>(
-                                widget::scrollable::Id::new("payload_scroll"),
-                                AbsoluteOffset { x: 0.0, y: 0.0 },
-                            );
+                            self.show_newest()
                         }
-                        scroll_command
+                    }
+                    ServerMessage::PayloadsDeleted => {
+                        self.refresh_payloads();
+                        Task::none()
+                    }
+                    ServerMessage::Listening(address) => {
+                        self.server_status = ServerStatus::Listening(address);
+                        Task::none()
+                    }
+                    ServerMessage::BindFailed(error) => {
+                        self.server_status = ServerStatus::Failed(error);
+                        Task::none()
                     }
                 }
             }
@@ -86,61 +150,380 @@ impl App {
                 }
                 Task::none()
             }
-            Message::TogglePayload(id) => {
-                if self.expanded_payload_id.as_ref() == Some(&id) {
-                    self.expanded_payload_id = None;
-                } else {
-                    if let Some(_old_id) = self.expanded_payload_id.take() {
-                         // No cache to update
-                         // self.update_highlight_cache(&old_id);
+            Message::ListenHostChanged(host) => {
+                self.listen_host_input = host;
+                Task::none()
+            }
+            Message::ListenPortChanged(port) => {
+                self.listen_port_input = port;
+                Task::none()
+            }
+            Message::ApplyListenAddress => {
+                let host = self.listen_host_input.trim();
+                let Ok(port) = self.listen_port_input.trim().parse::<u16>() else {
+                    return Task::none();
+                };
+                if host.is_empty() {
+                    return Task::none();
+                }
+
+                self.settings.set_listen_address(host.to_string(), port);
+                if let Err(e) = self.settings.save() {
+                    eprintln!("Failed to save settings: {e}");
+                }
+
+                // A new address changes the subscription identity, which restarts the server
+                let listen_address = ListenAddress::resolve(&self.settings, &self.args);
+                if listen_address != self.listen_address {
+                    self.listen_address = listen_address;
+                    self.server_status = ServerStatus::Starting;
+                }
+                Task::none()
+            }
+            Message::RetentionSizeChanged(size) => {
+                self.retention_inputs.size = size;
+                Task::none()
+            }
+            Message::RetentionCountChanged(count) => {
+                self.retention_inputs.count = count;
+                Task::none()
+            }
+            Message::RetentionAgeChanged(age) => {
+                self.retention_inputs.age = age;
+                Task::none()
+            }
+            Message::ApplyRetention => {
+                let Ok(retention) = self.retention_inputs.parse(self.settings.retention()) else {
+                    return Task::none();
+                };
+
+                self.settings.set_retention(retention.clone());
+                if let Err(e) = self.settings.save() {
+                    eprintln!("Failed to save settings: {e}");
+                }
+
+                match self.storage.set_retention(retention) {
+                    Ok(0) => Task::none(),
+                    Ok(evicted) => {
+                        self.evicted_count += evicted;
+                        self.refresh_payloads();
+                        self.save_payloads()
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to apply retention policy: {e}");
+                        Task::none()
                     }
-                    self.expanded_payload_id = Some(id.clone());
-                    // self.update_highlight_cache(&id);
                 }
+            }
+            Message::DismissEvictions => {
+                self.evicted_count = 0;
                 Task::none()
             }
-            Message::ToggleJsonSection(line_index) => {
-                 if let Some(_payload_id) = self.expanded_payload_id.clone() {
-                    if self.collapsed_json_lines.contains(&line_index) {
-                        self.collapsed_json_lines.remove(&line_index);
-                    } else {
-                        self.collapsed_json_lines.insert(line_index);
+            Message::TogglePayload(id) => {
+                if self.compare_mode {
+                    match self.compare_pick.take() {
+                        None => self.compare_pick = Some(id),
+                        // Clicking the picked payload again unpicks it
+                        Some(picked) if picked == id => {}
+                        Some(picked) => {
+                            self.show_diff(&picked, &id);
+                            self.compare_mode = false;
+                        }
                     }
-                    // No cache to update
-                    // self.update_highlight_cache(&payload_id);
-                 } else {
-                     eprintln!("WARN: ToggleJsonSection called with no expanded payload");
-                 }
+                    return Task::none();
+                }
+
+                // A payload toggled by hand is no longer collapsed when a newer one arrives
+                if self.auto_expanded_id.as_ref() == Some(&id) {
+                    self.auto_expanded_id = None;
+                }
+                if !self.expanded_payload_ids.remove(&id) {
+                    self.expanded_payload_ids.insert(id);
+                }
+                self.view_caches_stale = true;
+                Task::none()
+            }
+            Message::ToggleJsonSection(id, pointer) => {
+                // Tokenized lines do not depend on what is collapsed, so the cache stays valid
+                self.toggle_json_node(&id, pointer);
                 Task::none()
             }
+            Message::TogglePause => {
+                self.paused = !self.paused;
+                if self.paused {
+                    Task::none()
+                } else {
+                    self.release_held_payloads()
+                }
+            }
+            Message::ShowHeldPayloads => self.release_held_payloads(),
+            Message::ExpandAll => {
+                let ids = self.visible_payload_ids();
+                self.expanded_payload_ids.extend(ids);
+                self.view_caches_stale = true;
+                Task::none()
+            }
+            Message::CollapseAll => {
+                for id in self.visible_payload_ids() {
+                    self.expanded_payload_ids.remove(&id);
+                }
+                self.view_caches_stale = true;
+                Task::none()
+            }
+            Message::ExpansionChanged(expansion) => {
+                self.settings.set_expansion(expansion);
+                if let Err(e) = self.settings.save() {
+                    eprintln!("Failed to save settings: {e}");
+                }
+                Task::none()
+            }
+            Message::FollowTailToggled(follow) => {
+                self.settings.set_follow_tail(follow);
+                if let Err(e) = self.settings.save() {
+                    eprintln!("Failed to save settings: {e}");
+                }
+                Task::none()
+            }
+            Message::EditorCommandChanged(command) => {
+                self.editor_command_input = command;
+                Task::none()
+            }
+            Message::ApplyEditorCommand => {
+                let command = self.editor_command_input.trim();
+                if command.is_empty() {
+                    return Task::none();
+                }
+
+                self.settings.set_editor_command(command.to_string());
+                if let Err(e) = self.settings.save() {
+                    eprintln!("Failed to save settings: {e}");
+                }
+                Task::none()
+            }
+            Message::OpenOrigin(id) => {
+                if let Some(origin) = self.find_payload(&id).and_then(|payload| payload.annotations.origin.as_ref()) {
+                    self.editor_error = match editor::open(self.settings.editor_command(), origin) {
+                        Ok(()) => None,
+                        Err(e) => {
+                            eprintln!("Failed to open {origin} in the editor: {e}");
+                            Some(format!("Could not open {origin} in the editor: {e}"))
+                        }
+                    };
+                }
+                Task::none()
+            }
+            Message::DismissEditorError => {
+                self.editor_error = None;
+                Task::none()
+            }
+            Message::RememberCollapsedPathsToggled(remember) => {
+                self.settings.set_remember_collapsed_paths(remember);
+                if let Err(e) = self.settings.save() {
+                    eprintln!("Failed to save settings: {e}");
+                }
+                Task::none()
+            }
+            Message::ForgetCollapsedPaths => {
+                self.settings.clear_collapsed_paths();
+                self.remembered_paths_changed();
+                if let Err(e) = self.settings.save() {
+                    eprintln!("Failed to save settings: {e}");
+                }
+                Task::none()
+            }
+            Message::JsonScrolled(id, offset) => {
+                self.json_scroll.insert(id, offset);
+                Task::none()
+            }
+            Message::SelectJsonNode(id, pointer) => {
+                let selection = Some((id, pointer));
+                self.selected_json_node = if self.selected_json_node == selection {
+                    None
+                } else {
+                    selection
+                };
+                Task::none()
+            }
+            Message::Copy(id, target) => {
+                match self.find_payload(&id).and_then(|payload| clipboard::copy_text(payload, target)) {
+                    Some(contents) => iced::clipboard::write(contents),
+                    None => Task::none(),
+                }
+            }
             Message::ClearPayloads => {
-                if let Err(e) = self.storage.delete_all() {
+                // Pinned payloads survive clears; unpin them to remove them
+                if let Err(e) = self.storage.delete_all(true) {
                     eprintln!("Failed to clear payloads: {e}");
-                } else {
-                    self.payload_list_cache.clear();
-                    self.expanded_payload_id = None;
-                    self.collapsed_json_lines.clear();
+                    return Task::none();
                 }
+                // Pinned payloads that survived keep their expansion, collapsed nodes and scroll
+                self.refresh_payloads();
+                self.save_payloads()
+            }
+            Message::ClearChannel(channel) => {
+                if let Err(e) = self.storage.delete_channel(&channel, true) {
+                    eprintln!("Failed to clear channel '{channel}': {e}");
+                    return Task::none();
+                }
+                // Pinned payloads that survived keep their expansion, collapsed nodes and scroll
+                self.refresh_payloads();
+                self.save_payloads()
+            }
+            Message::SearchChanged(input) => {
+                // Keep the last valid query active while an expression is being typed
+                match SearchQuery::parse(&input) {
+                    Ok(query) => {
+                        self.search_query = query;
+                        self.search_error = None;
+                        self.refresh_search_matches();
+                    }
+                    Err(e) => self.search_error = Some(e.to_string()),
+                }
+                self.search_input = input;
+                Task::none()
+            }
+            Message::SelectChannel(channel) => {
+                self.selected_channel = channel;
+                self.expand_newest();
                 Task::none()
             }
-            Message::DeletePayload(id) => {
-                let deleted = match self.storage.delete(&id) {
-                    Ok(d) => d,
+            Message::DeletePayload(id) => match self.storage.delete(&id) {
+                Ok(true) => {
+                    self.uncache(&[id]);
+                    self.save_payloads()
+                }
+                Ok(false) => Task::none(),
+                Err(e) => {
+                    eprintln!("Failed to delete payload: {e}");
+                    Task::none()
+                }
+            },
+            Message::TogglePin(id) => {
+                let Some(pinned) = self.find_payload(&id).map(|payload| !payload.pinned) else {
+                    return Task::none();
+                };
+                match self.storage.set_pinned(&id, pinned) {
+                    Ok(true) => {
+                        if let Some(payload) = self.payload_list_cache.iter_mut().find(|payload| payload.id == id) {
+                            payload.pinned = pinned;
+                        }
+                        self.save_payloads()
+                    }
+                    Ok(false) => Task::none(),
                     Err(e) => {
-                        eprintln!("Failed to delete payload: {e}");
-                        false
+                        eprintln!("Failed to pin payload: {e}");
+                        Task::none()
                     }
-                };
+                }
+            }
+            Message::PayloadsSaved(result) => {
+                if let Err(e) = result {
+                    eprintln!("Failed to save payloads: {e}");
+                }
+                Task::none()
+            }
+            Message::ToggleCompareMode => {
+                self.compare_mode = !self.compare_mode;
+                self.compare_pick = None;
+                Task::none()
+            }
+            Message::DiffWithPrevious(id) => {
+                // The cache is newest first, so the previous payload comes after this one
+                let previous = self
+                    .payload_list_cache
+                    .iter()
+                    .position(|payload| payload.id == id)
+                    .and_then(|index| {
+                        let channel = &self.payload_list_cache[index].channel;
+                        self.payload_list_cache[index + 1..]
+                            .iter()
+                            .find(|payload| &payload.channel == channel)
+                    })
+                    .map(|payload| payload.id.clone());
 
-                if deleted {
-                    self.payload_list_cache.retain(|(item_id, _)| item_id != &id);
-                    if self.expanded_payload_id.as_ref() == Some(&id) {
-                        self.expanded_payload_id = None;
+                match previous {
+                    Some(previous) => self.show_diff(&previous, &id),
+                    None => eprintln!("INFO: No earlier payload in the channel of {id} to diff against"),
+                }
+                Task::none()
+            }
+            Message::CloseDiff => {
+                self.diff_ids = None;
+                self.diff = None;
+                Task::none()
+            }
+            Message::ToggleExport => {
+                self.show_export = !self.show_export;
+                self.export_status = None;
+                Task::none()
+            }
+            Message::Export(format) => {
+                // Serialized and written off the UI thread, so large exports do not freeze the window
+                let payloads = self.visible_payloads().into_iter().cloned().collect::<Vec<_>>();
+                let path = export::default_path(format);
+                self.export_status = None;
+
+                let write = tokio::task::spawn_blocking(move || {
+                    let payloads = payloads.iter().collect::<Vec<_>>();
+                    let result = export::export(&payloads, format)
+                        .map_err(|e| e.to_string())
+                        .and_then(|contents| std::fs::write(&path, contents).map_err(|e| e.to_string()));
+                    match result {
+                        Ok(()) => Ok(path.display().to_string()),
+                        Err(e) => {
+                            eprintln!("Failed to export payloads to {path:?}: {e}");
+                            Err(format!("Export failed: {e}"))
+                        }
                     }
-                    self.collapsed_json_lines.clear();
+                });
+                Task::perform(write, |result| {
+                    Message::Exported(result.unwrap_or_else(|e| Err(format!("Export failed: {e}"))))
+                })
+            }
+            Message::Exported(result) => {
+                self.export_status = Some(result);
+                Task::none()
+            }
+            Message::ToggleImport => {
+                self.show_import = !self.show_import;
+                self.import_status = None;
+                if self.show_import {
+                    // Default to the open channel; clearing it imports into a new one
+                    self.import_channel_input = self.selected_channel.clone().unwrap_or_default();
                 }
                 Task::none()
             }
+            Message::ImportPathChanged(path) => {
+                self.import_path_input = path;
+                Task::none()
+            }
+            Message::ImportChannelChanged(channel) => {
+                self.import_channel_input = channel;
+                Task::none()
+            }
+            Message::Import => {
+                let path = PathBuf::from(self.import_path_input.trim());
+                self.import_file(path)
+            }
+            Message::Imported(channel, result) => {
+                self.import_status = Some(match result {
+                    Ok(summary) => {
+                        self.evicted_count += summary.evicted;
+                        self.refresh_payloads();
+                        if self.channel_list_cache.iter().any(|(name, _)| *name == channel) {
+                            self.selected_channel = Some(channel.clone());
+                        }
+                        Ok(format!("Imported {} payloads into {channel}", summary.imported))
+                    }
+                    Err(e) => Err(format!("Import failed: {e}")),
+                });
+                Task::none()
+            }
+            Message::FileDropped(path) => {
+                self.show_import = true;
+                self.import_path_input = path.display().to_string();
+                self.import_file(path)
+            }
             Message::Event(event) => match event {
                 Event::Keyboard(keyboard::Event::KeyPressed {
                     key: keyboard::Key::Named(key::Named::Tab),
@@ -158,8 +541,36 @@ impl App {
                     ..
                 }) => {
                     self.hide_modal();
+                    self.selected_json_node = None;
                     Task::none()
                 }
+                // Ctrl/Cmd+C copies the selected node, or the topmost expanded payload;
+                // with Shift it copies the selected node's JSONPath, with Alt the compact payload
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key: keyboard::Key::Character(c),
+                    modifiers,
+                    ..
+                }) if modifiers.command() && c.as_str().eq_ignore_ascii_case("c") => {
+                    let selected = self
+                        .selected_json_node
+                        .clone()
+                        .filter(|(id, _)| self.expanded_payload_ids.contains(id));
+                    let (id, pointer) = match selected {
+                        Some((id, pointer)) => (id, Some(pointer)),
+                        None => match self.visible_payload_ids().into_iter().find(|id| self.expanded_payload_ids.contains(id)) {
+                            Some(id) => (id, None),
+                            None => return Task::none(),
+                        },
+                    };
+
+                    let target = match pointer {
+                        _ if modifiers.alt() => CopyTarget::Compact,
+                        Some(pointer) if modifiers.shift() => CopyTarget::Path(pointer),
+                        Some(pointer) => CopyTarget::Value(pointer),
+                        None => CopyTarget::Pretty,
+                    };
+                    self.update(Message::Copy(id, target))
+                }
                 _ => Task::none(),
             },
             Message::WindowMoved(position) => {
@@ -184,7 +595,13 @@ impl App {
                     // Log quietly if needed, but main saves happen earlier.
                     eprintln!("Note: Final settings save on close failed: {e}");
                 }
-                iced::exit()
+                // Payload changes are written in batches; write the last one before exiting
+                Task::future(self.storage.flush()).then(|result| {
+                    if let Err(e) = result {
+                        eprintln!("Failed to save payloads on close: {e}");
+                    }
+                    iced::exit()
+                })
             }
         }
     }
@@ -218,7 +635,19 @@ impl App {
         .height(Fill);
 
         let button_size = 25;
-        let payload_count = self.payload_list_cache.len();
+        let visible_payloads = self.visible_payloads();
+        let channel_total = match &self.selected_channel {
+            Some(channel) => self
+                .channel_list_cache
+                .iter()
+                .find(|(name, _)| name == channel)
+                .map_or(0, |(_, count)| *count),
+            None => self.payload_list_cache.len(),
+        };
+        let clear_message = match &self.selected_channel {
+            Some(channel) => Message::ClearChannel(channel.clone()),
+            None => Message::ClearPayloads,
+        };
 
         let content = container(
             column![
@@ -227,15 +656,42 @@ impl App {
                         .width(button_size)
                         .height(button_size)
                         .padding(3.0),
+                    components::channel_tabs(
+                        &self.channel_list_cache,
+                        self.payload_list_cache.len(),
+                        self.selected_channel.as_ref(),
+                    ),
                     horizontal_space(),
-                    text(format!("{payload_count}"))
-                        .size(14),
+                    button(text("Expand all").size(12))
+                        .style(button::secondary)
+                        .height(button_size)
+                        .on_press(Message::ExpandAll),
+                    button(text("Collapse all").size(12))
+                        .style(button::secondary)
+                        .height(button_size)
+                        .on_press(Message::CollapseAll),
+                    button(text(if self.paused { "Resume" } else { "Pause" }).size(12))
+                        .style(if self.paused { button::primary } else { button::secondary })
+                        .height(button_size)
+                        .on_press(Message::TogglePause),
+                    button(text("Import").size(12))
+                        .style(if self.show_import { button::primary } else { button::secondary })
+                        .height(button_size)
+                        .on_press(Message::ToggleImport),
+                    button(text("Export").size(12))
+                        .style(if self.show_export { button::primary } else { button::secondary })
+                        .height(button_size)
+                        .on_press(Message::ToggleExport),
+                    button(text("Compare").size(12))
+                        .style(if self.compare_mode { button::primary } else { button::secondary })
+                        .height(button_size)
+                        .on_press(Message::ToggleCompareMode),
                     button(remove_all_svg)
                         .style(button::danger)
                         .width(button_size)
                         .height(button_size)
                         .padding(3.0)
-                        .on_press(Message::ClearPayloads),
+                        .on_press(clear_message),
                     button(settings_svg)
                         .style(button::secondary)
                         .width(button_size)
@@ -247,12 +703,52 @@ impl App {
                 .spacing(10)
                 .align_y(iced::alignment::Vertical::Center)
                 .height(Length::Shrink),
-                components::payload_list(
-                    &self.payload_list_cache,
-                    self.expanded_payload_id.as_ref(),
-                    &self.theme(),
-                    &self.collapsed_json_lines,
+                components::server_error(&self.server_status),
+                components::eviction_notice(self.evicted_count, self.settings.retention()),
+                components::editor_error(self.editor_error.as_deref()),
+                components::held_banner(self.held_payload_ids.len(), self.paused),
+                components::search_bar(
+                    &self.search_input,
+                    self.search_error.as_deref(),
+                    self.search_query.as_ref().map(|_| (visible_payloads.len(), channel_total)),
                 ),
+                if self.show_import {
+                    components::import_bar(
+                        &self.import_path_input,
+                        &self.import_channel_input,
+                        self.import_status.as_ref(),
+                    )
+                } else {
+                    column![].into()
+                },
+                if self.show_export {
+                    components::export_bar(visible_payloads.len(), self.export_status.as_ref())
+                } else {
+                    column![].into()
+                },
+                if self.compare_mode {
+                    components::compare_banner(self.compare_pick.as_ref().and_then(|id| self.find_payload(id)))
+                } else {
+                    column![].into()
+                },
+                match self.diff_payloads() {
+                    Some((old, new, diff)) => components::diff_view(old, new, diff, &self.theme()),
+                    None => components::payload_list(
+                        visible_payloads,
+                        &self.expanded_payload_ids,
+                        &self.theme(),
+                        JsonViewState {
+                            collapsed_nodes: &self.collapsed_node_cache,
+                            selected_node: self.selected_json_node.as_ref(),
+                            scroll_offsets: &self.json_scroll,
+                            // Leave room for the header, bars and the payload's own controls
+                            max_height: (self.settings.get_window_size().height - 220.0).max(240.0),
+                            cache: &self.highlight_cache,
+                            path_hits: &self.search_matches,
+                        },
+                        self.search_query.as_ref(),
+                    ),
+                },
                 row![horizontal_space()]
                     .align_y(Bottom)
                     .height(Length::Shrink),
@@ -262,7 +758,16 @@ impl App {
 
         if self.show_modal {
             let current_theme = self.theme();
-            let settings_content = components::settings_modal(current_theme);
+            let settings_content = components::settings_modal(
+                current_theme,
+                &self.listen_address,
+                &self.server_status,
+                &self.listen_host_input,
+                &self.listen_port_input,
+                &self.retention_inputs,
+                &self.editor_command_input,
+                &self.settings,
+            );
 
             components::modal(content, settings_content, Message::HideModal)
         } else {
@@ -274,4 +779,295 @@ impl App {
     fn hide_modal(&mut self) {
         self.show_modal = false;
     }
+
+    /// Reloads the cached payload list and channel counts from storage
+    fn refresh_payloads(&mut self) {
+        self.payload_list_cache = self.storage.get_all();
+        self.channel_list_cache = self.storage.channels();
+        self.refresh_search_matches();
+        self.forget_removed();
+    }
+
+    /// Runs the search over every cached payload, after the query or the whole list changed
+    fn refresh_search_matches(&mut self) {
+        self.search_matches = match &self.search_query {
+            Some(query) => self
+                .payload_list_cache
+                .iter()
+                .filter_map(|payload| Some((payload.id.clone(), query.hits(&payload.value)?)))
+                .collect(),
+            None => HashMap::new(),
+        };
+    }
+
+    /// Adds a payload the server stored to the cached list and drops the payloads
+    /// it evicted, rather than reloading every payload on each arrival
+    fn cache_added(&mut self, added: &Added) {
+        // Already listed when the list was reloaded after the payload was stored
+        if self.find_payload(&added.payload.id).is_none() {
+            // Ids only grow, so the payload is the newest
+            self.payload_list_cache.insert(0, added.payload.clone());
+            self.view_caches_stale = true;
+            if let Some(hits) = self.search_query.as_ref().and_then(|query| query.hits(&added.payload.value)) {
+                self.search_matches.insert(added.payload.id.clone(), hits);
+            }
+            let channel = &added.payload.channel;
+            match self.channel_list_cache.binary_search_by(|(name, _)| name.cmp(channel)) {
+                Ok(index) => self.channel_list_cache[index].1 += 1,
+                Err(index) => self.channel_list_cache.insert(index, (channel.clone(), 1)),
+            }
+        }
+        self.uncache(&added.evicted);
+    }
+
+    /// Drops deleted or evicted payloads from the cached list and channel counts
+    fn uncache(&mut self, ids: &[String]) {
+        if ids.is_empty() {
+            return;
+        }
+
+        let channels = &mut self.channel_list_cache;
+        self.payload_list_cache.retain(|payload| {
+            if !ids.contains(&payload.id) {
+                return true;
+            }
+            if let Some(index) = channels.iter().position(|(name, _)| *name == payload.channel) {
+                channels[index].1 -= 1;
+                if channels[index].1 == 0 {
+                    channels.remove(index);
+                }
+            }
+            false
+        });
+        self.forget_removed();
+    }
+
+    /// Forgets the view state of payloads no longer in the cached list
+    fn forget_removed(&mut self) {
+        let ids = self
+            .payload_list_cache
+            .iter()
+            .map(|payload| payload.id.as_str())
+            .collect::<HashSet<_>>();
+        self.expanded_payload_ids.retain(|id| ids.contains(id.as_str()));
+        self.auto_expanded_id = self.auto_expanded_id.take().filter(|id| ids.contains(id.as_str()));
+        self.json_scroll.retain(|id, _| ids.contains(id.as_str()));
+        self.collapsed_json_nodes.retain(|id, _| ids.contains(id.as_str()));
+        self.held_payload_ids.retain(|id| ids.contains(id.as_str()));
+        self.search_matches.retain(|id, _| ids.contains(id.as_str()));
+        self.view_caches_stale = true;
+
+        // Fall back to all channels once the selected one has been emptied
+        if let Some(channel) = &self.selected_channel {
+            if !self.channel_list_cache.iter().any(|(name, _)| name == channel) {
+                self.selected_channel = None;
+            }
+        }
+    }
+
+    /// Imports a file into the channel from the import bar off the UI thread,
+    /// then shows that channel once `Message::Imported` arrives
+    fn import_file(&mut self, path: PathBuf) -> Task<Message> {
+        let channel = import::target_channel(&path, Some(&self.import_channel_input));
+        self.import_status = Some(Ok(format!("Importing into {channel}")));
+
+        let storage = self.storage.clone();
+        let import = {
+            let channel = channel.clone();
+            async move {
+                let summary = tokio::task::spawn_blocking({
+                    let storage = storage.clone();
+                    let path = path.clone();
+                    move || import::read_file(&path, &channel).and_then(|items| storage.import(items))
+                })
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+                .inspect_err(|e| eprintln!("Failed to import payloads from {path:?}: {e}"))?;
+                storage.flush().await?;
+                Ok::<ImportSummary, io::Error>(summary)
+            }
+        };
+
+        Task::perform(import, move |result| Message::Imported(channel, result.map_err(|e| e.to_string())))
+    }
+
+    /// Reports once the changes just made to the stored payloads are on disk
+    fn save_payloads(&self) -> Task<Message> {
+        Task::perform(self.storage.flush(), |result| {
+            Message::PayloadsSaved(result.map_err(|e| e.to_string()))
+        })
+    }
+
+    /// Expands the visible payloads the expansion setting asks for
+    fn expand_newest(&mut self) {
+        self.view_caches_stale = true;
+        match self.settings.expansion() {
+            Expansion::ExpandNewest => {
+                let Some(newest) = self.visible_payload_ids().into_iter().next() else {
+                    return;
+                };
+                if self.auto_expanded_id.as_ref() == Some(&newest) {
+                    return;
+                }
+                // Payloads expanded by hand stay open; only the previous automatic one collapses
+                if let Some(previous) = self.auto_expanded_id.take() {
+                    self.expanded_payload_ids.remove(&previous);
+                }
+                if self.expanded_payload_ids.insert(newest.clone()) {
+                    self.auto_expanded_id = Some(newest);
+                }
+            }
+            Expansion::ExpandAll => {
+                let ids = self.visible_payload_ids();
+                self.expanded_payload_ids.extend(ids);
+            }
+            Expansion::CollapseAll => {}
+        }
+    }
+
+    /// Reacts to new payloads becoming visible: expands them and scrolls back
+    /// to the newest, as far as the incoming settings allow
+    fn show_newest(&mut self) -> Task<Message> {
+        self.expand_newest();
+
+        if self.settings.follow_tail() {
+            widget::scrollable::scroll_to::<Message>(
+                widget::scrollable::Id::new("payload_scroll"),
+                AbsoluteOffset { x: 0.0, y: 0.0 },
+            )
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Adds the payloads held back while paused to the list
+    fn release_held_payloads(&mut self) -> Task<Message> {
+        if self.held_payload_ids.is_empty() {
+            return Task::none();
+        }
+        self.held_payload_ids.clear();
+        self.show_newest()
+    }
+
+    /// JSON pointers of the collapsed nodes in `payload`: those matching a remembered
+    /// path, adjusted by what was collapsed or expanded by hand in this payload
+    fn collapsed_nodes(&self, payload: &Payload) -> HashSet<String> {
+        let mut collapsed = HashSet::new();
+        for query in &self.remembered_paths {
+            collapsed.extend(query.path_hits(&payload.value));
+        }
+        for (pointer, is_collapsed) in self.collapsed_json_nodes.get(&payload.id).into_iter().flatten() {
+            if *is_collapsed {
+                collapsed.insert(pointer.clone());
+            } else {
+                collapsed.remove(pointer);
+            }
+        }
+        collapsed
+    }
+
+    /// Collapses or expands a node. With "remember collapsed paths" on, the node's path,
+    /// array indexes generalized, is remembered or forgotten for every payload instead.
+    fn toggle_json_node(&mut self, id: &str, pointer: String) {
+        let Some(payload) = self.find_payload(id) else {
+            return;
+        };
+        let collapse = !self.collapsed_nodes(payload).contains(&pointer);
+
+        if self.settings.remember_collapsed_paths() {
+            if let Some(path) = search::path_from_pointer(&payload.value, &pointer) {
+                let shape = path
+                    .into_iter()
+                    .map(|segment| match segment {
+                        Segment::Index(_) => Segment::Wildcard,
+                        segment => segment,
+                    })
+                    .collect::<Vec<_>>();
+                self.settings.set_path_collapsed(search::format_path(&shape), collapse);
+                self.remembered_paths_changed();
+                if let Err(e) = self.settings.save() {
+                    eprintln!("Failed to save settings: {e}");
+                }
+                if let Some(overrides) = self.collapsed_json_nodes.get_mut(id) {
+                    overrides.remove(&pointer);
+                }
+            }
+        }
+
+        // Without remembering, or when a broader remembered path still disagrees, override this payload only
+        let collapsed = self
+            .find_payload(id)
+            .is_some_and(|payload| self.collapsed_nodes(payload).contains(&pointer));
+        if collapsed != collapse {
+            self.collapsed_json_nodes
+                .entry(id.to_string())
+                .or_default()
+                .insert(pointer, collapse);
+        }
+        self.collapsed_node_cache.remove(id);
+        self.view_caches_stale = true;
+    }
+
+    /// Re-parses the remembered paths after they changed, which changes every payload's collapsed nodes
+    fn remembered_paths_changed(&mut self) {
+        self.remembered_paths = parse_remembered_paths(&self.settings);
+        self.collapsed_node_cache.clear();
+        self.view_caches_stale = true;
+    }
+
+    /// Works out the collapsed nodes of payloads expanded since the last update,
+    /// and forgets those of payloads collapsed or deleted since
+    fn update_collapsed_nodes(&mut self) {
+        let mut cache = std::mem::take(&mut self.collapsed_node_cache);
+        cache.retain(|id, _| self.expanded_payload_ids.contains(id));
+        for payload in self
+            .payload_list_cache
+            .iter()
+            .filter(|payload| self.expanded_payload_ids.contains(&payload.id))
+        {
+            if !cache.contains_key(&payload.id) {
+                cache.insert(payload.id.clone(), self.collapsed_nodes(payload));
+            }
+        }
+        self.collapsed_node_cache = cache;
+    }
+
+    fn find_payload(&self, id: &str) -> Option<&Payload> {
+        self.payload_list_cache.iter().find(|payload| payload.id == id)
+    }
+
+    /// Shows the diff between two payloads, the older one as the base
+    fn show_diff(&mut self, first: &str, second: &str) {
+        let (Some(first), Some(second)) = (self.find_payload(first), self.find_payload(second)) else {
+            return;
+        };
+        let (old, new) = if first.received_at <= second.received_at {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let diff = diff::diff(&old.value, &new.value);
+        self.diff_ids = Some((old.id.clone(), new.id.clone()));
+        self.diff = Some(diff);
+    }
+
+    /// The payloads and diff being shown, unless either payload has been deleted since
+    fn diff_payloads(&self) -> Option<(&Payload, &Payload, &Diff)> {
+        let (old, new) = self.diff_ids.as_ref()?;
+        Some((self.find_payload(old)?, self.find_payload(new)?, self.diff.as_ref()?))
+    }
+
+    /// Payloads in the selected channel (all channels when none is selected) matching the search
+    fn visible_payloads(&self) -> Vec<&Payload> {
+        self.payload_list_cache
+            .iter()
+            .filter(|payload| self.selected_channel.as_ref().is_none_or(|channel| payload.channel == *channel))
+            .filter(|payload| self.search_query.is_none() || self.search_matches.contains_key(&payload.id))
+            .filter(|payload| !self.held_payload_ids.contains(&payload.id))
+            .collect()
+    }
+
+    fn visible_payload_ids(&self) -> Vec<String> {
+        self.visible_payloads().into_iter().map(|payload| payload.id.clone()).collect()
+    }
 }
@@ -0,0 +1,308 @@
+use crate::access::AllowedOrigins;
+use crate::cli::{Args, ColorChoice, OutputFormat};
+use crate::export::{self, ExportFormat};
+use crate::import;
+use crate::payload::{decode_hex, Payload, PayloadKind, Severity, DEFAULT_CHANNEL};
+use crate::search::{self, SearchQuery};
+use crate::server::{self, ListenAddress, ServerConfig, ServerMessage};
+use crate::settings::Settings;
+use crate::storage::Storage;
+use futures::StreamExt;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+
+// Most bytes of a binary payload shown in the hex dump
+const HEX_PREVIEW_BYTES: usize = 256;
+
+// ANSI styles for the terminal output
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const KEY: &str = "\x1b[34m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const LITERAL: &str = "\x1b[35m";
+const CHANNEL: &str = "\x1b[36m";
+const WARN: &str = "\x1b[33;1m";
+const ERROR: &str = "\x1b[31;1m";
+
+/// Escapes control characters in text from senders, such as ESC, so a payload cannot
+/// move the cursor, recolor or retitle the terminal. With `keep_lines`, newlines and
+/// tabs are left alone for multi-line bodies.
+fn escape_controls(text: &str, keep_lines: bool) -> Cow<'_, str> {
+    let escaped = |c: char| c.is_control() && !(keep_lines && matches!(c, '\n' | '\t'));
+    if !text.chars().any(escaped) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if escaped(c) {
+            out.extend(c.escape_unicode());
+        } else {
+            out.push(c);
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Writes terminal output, with or without ANSI colors
+struct Printer {
+    format: OutputFormat,
+    color: bool,
+}
+
+impl Printer {
+    fn new(args: &Args) -> Self {
+        let color = match args.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+        };
+        Self {
+            format: args.format,
+            color,
+        }
+    }
+
+    /// Appends `content`, which may come from a sender, with its control characters escaped
+    fn paint(&self, out: &mut String, style: &str, content: &str) {
+        let content = escape_controls(content, false);
+        if self.color {
+            out.push_str(style);
+            out.push_str(&content);
+            out.push_str(RESET);
+        } else {
+            out.push_str(&content);
+        }
+    }
+
+    /// Renders JSON, indented by `indent` levels when pretty and on one line otherwise
+    fn json(&self, out: &mut String, value: &Value, indent: Option<usize>) {
+        let newline = |out: &mut String, level: usize| {
+            out.push('\n');
+            out.push_str(&"  ".repeat(level));
+        };
+
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                out.push('{');
+                for (index, (key, child)) in map.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    match indent {
+                        Some(level) => newline(out, level + 1),
+                        None if index > 0 => out.push(' '),
+                        None => {}
+                    }
+                    self.paint(out, KEY, &Value::String(key.clone()).to_string());
+                    out.push_str(": ");
+                    self.json(out, child, indent.map(|level| level + 1));
+                }
+                if let Some(level) = indent {
+                    newline(out, level);
+                }
+                out.push('}');
+            }
+            Value::Array(items) if !items.is_empty() => {
+                out.push('[');
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    match indent {
+                        Some(level) => newline(out, level + 1),
+                        None if index > 0 => out.push(' '),
+                        None => {}
+                    }
+                    self.json(out, item, indent.map(|level| level + 1));
+                }
+                if let Some(level) = indent {
+                    newline(out, level);
+                }
+                out.push(']');
+            }
+            Value::String(_) => self.paint(out, STRING, &value.to_string()),
+            Value::Number(_) => self.paint(out, NUMBER, &value.to_string()),
+            Value::Bool(_) | Value::Null => self.paint(out, LITERAL, &value.to_string()),
+            empty => out.push_str(&empty.to_string()),
+        }
+    }
+
+    fn header(&self, out: &mut String, payload: &Payload) {
+        self.paint(
+            out,
+            DIM,
+            &payload.received_at.format("%H:%M:%S%.3f").to_string(),
+        );
+        if payload.channel != DEFAULT_CHANNEL {
+            out.push(' ');
+            self.paint(out, CHANNEL, &format!("#{}", payload.channel));
+        }
+        if let Some(severity) = payload.annotations.severity {
+            let style = match severity {
+                Severity::Info => DIM,
+                Severity::Warn => WARN,
+                Severity::Error => ERROR,
+            };
+            out.push(' ');
+            self.paint(out, style, &severity.as_str().to_uppercase());
+        }
+        if let Some(label) = &payload.annotations.label {
+            out.push(' ');
+            self.paint(out, BOLD, label);
+        }
+        if let Some(request) = &payload.request {
+            out.push(' ');
+            self.paint(out, BOLD, &request.summary());
+        }
+        if let Some(origin) = &payload.annotations.origin {
+            out.push(' ');
+            self.paint(out, DIM, &format!("at {origin}"));
+        }
+    }
+
+    fn body(&self, out: &mut String, payload: &Payload) {
+        let pretty = self.format == OutputFormat::Pretty;
+        match (payload.kind, &payload.value) {
+            (PayloadKind::Text, Value::String(body)) if pretty => out.push_str(&escape_controls(body, true)),
+            // JSON escaping leaves DEL and C1 controls such as CSI as they are
+            (PayloadKind::Text, Value::String(body)) => {
+                out.push_str(&escape_controls(&Value::String(body.clone()).to_string(), false));
+            }
+            (PayloadKind::Binary, Value::String(hex)) => {
+                let bytes = decode_hex(hex).unwrap_or_default();
+                self.paint(out, DIM, &format!("<{} bytes>", bytes.len()));
+                if pretty {
+                    for (index, chunk) in bytes[..bytes.len().min(HEX_PREVIEW_BYTES)]
+                        .chunks(16)
+                        .enumerate()
+                    {
+                        out.push_str(&format!("\n{:08x}  ", index * 16));
+                        let hex = chunk
+                            .iter()
+                            .map(|byte| format!("{byte:02x}"))
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        self.paint(out, NUMBER, &hex);
+                    }
+                }
+            }
+            (_, value) => self.json(out, value, pretty.then_some(0)),
+        }
+    }
+
+    fn payload(&self, payload: &Payload) -> String {
+        let mut out = String::new();
+        self.header(&mut out, payload);
+        match self.format {
+            OutputFormat::Pretty => out.push('\n'),
+            OutputFormat::Compact => out.push(' '),
+        }
+        self.body(&mut out, payload);
+        if self.format == OutputFormat::Pretty {
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Runs the payload server without a window, storing payloads like the GUI
+/// does and printing each one to stdout until interrupted
+pub fn serve(args: &Args) -> io::Result<()> {
+    let settings = Settings::load();
+    let storage = Storage::new(settings.retention().clone())?;
+    let address = ListenAddress::resolve(&settings, args);
+    let origins = AllowedOrigins::new(settings.allowed_origins());
+    let printer = Printer::new(args);
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let mut messages = std::pin::pin!(server::listen(&ServerConfig {
+            address,
+            storage: storage.clone(),
+            origins,
+        }));
+
+        loop {
+            let message = tokio::select! {
+                message = messages.next() => message,
+                _ = tokio::signal::ctrl_c() => None,
+            };
+
+            match message {
+                Some(ServerMessage::PayloadReceived(added)) => {
+                    if !added.evicted.is_empty() {
+                        eprintln!(
+                            "INFO: Retention policy evicted {} older payloads",
+                            added.evicted.len()
+                        );
+                    }
+
+                    let mut stdout = io::stdout().lock();
+                    writeln!(stdout, "{}", printer.payload(&added.payload))?;
+                    stdout.flush()?;
+                }
+                // The server itself logs the bound address, and storage logs deletions
+                Some(ServerMessage::Listening(_) | ServerMessage::PayloadsDeleted) => {}
+                Some(ServerMessage::BindFailed(error)) => {
+                    return Err(io::Error::new(io::ErrorKind::AddrNotAvailable, error));
+                }
+                // Changes are written in batches, so write the last one before exiting
+                None => return storage.flush().await,
+            }
+        }
+    })
+}
+
+/// Exports stored payloads, filtered like the GUI list, to `--output` or stdout
+pub fn export(args: &Args, format: ExportFormat) -> io::Result<()> {
+    let query = match args.search.as_deref().map(SearchQuery::parse) {
+        Some(Err(e)) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e.to_string())),
+        Some(Ok(query)) => query,
+        None => None,
+    };
+
+    // Read-only, so exporting is safe while the GUI or `serve` is running
+    let payloads = Storage::read_only()?;
+    let selected = search::filter_payloads(&payloads, args.channel.as_deref(), query.as_ref());
+    let contents = export::export(&selected, format).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, contents)?;
+            eprintln!("INFO: Exported {} payloads to {}", selected.len(), path.display());
+        }
+        None => io::stdout().lock().write_all(contents.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Imports the payloads in `file` into `--channel`, or a channel named after the file.
+/// Writes to the store, so it fails while the GUI or `serve` has it open.
+pub fn import(args: &Args, file: &Path) -> io::Result<()> {
+    let channel = import::target_channel(file, args.channel.as_deref());
+    let items = import::read_file(file, &channel)?;
+
+    let settings = Settings::load();
+    let storage = Storage::new(settings.retention().clone())?;
+    let summary = storage.import(items)?;
+    futures::executor::block_on(storage.flush())?;
+
+    eprintln!(
+        "INFO: Imported {} payloads from {} into channel '{channel}'",
+        summary.imported,
+        file.display()
+    );
+    if summary.evicted > 0 {
+        eprintln!(
+            "INFO: Retention policy evicted {} payloads",
+            summary.evicted
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,479 @@
+use crate::body;
+use crate::envelope;
+use crate::payload::{decode_hex, Incoming, Payload, PayloadKind, RequestMeta};
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use std::io;
+use std::path::Path;
+
+/// Channel imports land in when no channel is given and the file name is unusable
+pub const DEFAULT_IMPORT_CHANNEL: &str = "imported";
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The channel to import `path` into: `channel` when given, otherwise the file name
+pub fn target_channel(path: &Path, channel: Option<&str>) -> String {
+    channel
+        .map(str::trim)
+        .filter(|channel| !channel.is_empty())
+        .or_else(|| path.file_stem().and_then(|stem| stem.to_str()))
+        .map_or_else(|| DEFAULT_IMPORT_CHANNEL.to_string(), str::to_string)
+}
+
+/// Decodes standard or URL-safe base64, as used for binary HAR bodies
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let digit = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(digit);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Reads a HAR `[{ "name": ..., "value": ... }]` list into pairs
+fn name_values(list: Option<&Value>) -> Vec<(String, String)> {
+    list.and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let name = item.get("name")?.as_str()?;
+                    let value = item
+                        .get("value")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    Some((name.to_string(), value.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Splits a HAR request URL into its path and query
+fn split_url(url: &str) -> (String, Option<String>) {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path_and_query = without_scheme
+        .find('/')
+        .map_or("/", |start| &without_scheme[start..]);
+    let path_and_query = path_and_query.split('#').next().unwrap_or_default();
+    match path_and_query.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (path_and_query.to_string(), None),
+    }
+}
+
+/// Decodes a HAR `postData` or `content` object into payloads. `hex` marks bodies
+/// written by our own HAR export, which hex encodes binary bodies.
+fn har_body(content: &Value, hex: bool) -> Vec<(PayloadKind, Value)> {
+    let mime_type = content.get("mimeType").and_then(Value::as_str);
+
+    let Some(text) = content.get("text").and_then(Value::as_str) else {
+        // Form posts may only be recorded as a list of fields
+        let params = name_values(content.get("params"));
+        if params.is_empty() {
+            return Vec::new();
+        }
+        let mut fields = Map::new();
+        for (name, value) in params {
+            fields.insert(name, Value::String(value));
+        }
+        return vec![(PayloadKind::Form, Value::Object(fields))];
+    };
+
+    let bytes = if content.get("encoding").and_then(Value::as_str) == Some("base64") {
+        decode_base64(text)
+    } else if hex {
+        decode_hex(text)
+    } else {
+        None
+    }
+    .unwrap_or_else(|| text.as_bytes().to_vec());
+
+    body::decode(mime_type, &bytes)
+}
+
+/// Takes the request body of every HAR entry, and the response body of entries
+/// that did not come from our own export, whose responses only describe what the
+/// app stored (`{"id": ..., "channel": ..., ...}`) rather than anything the sender sent
+fn har_payloads(
+    entries: &[Value],
+    channel: &str,
+    now: DateTime<Utc>,
+) -> Vec<(Incoming, DateTime<Utc>)> {
+    let mut payloads = Vec::new();
+
+    for entry in entries {
+        let received_at = entry
+            .get("startedDateTime")
+            .and_then(Value::as_str)
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map_or(now, |time| time.with_timezone(&Utc));
+        let from_dbug = entry
+            .get("comment")
+            .and_then(Value::as_str)
+            .is_some_and(|comment| comment.starts_with("dbug payload "));
+
+        let request = entry.get("request").unwrap_or(&Value::Null);
+        let method = request
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or("GET");
+        let (path, query) = split_url(request.get("url").and_then(Value::as_str).unwrap_or("/"));
+        let remote_addr = entry
+            .get("serverIPAddress")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let mut bodies = Vec::new();
+        if let Some(post_data) = request.get("postData") {
+            let hex = from_dbug
+                && post_data.get("comment").and_then(Value::as_str) == Some("Body is hex encoded");
+            let meta = RequestMeta {
+                method: method.to_string(),
+                path: path.clone(),
+                query: query.clone(),
+                headers: name_values(request.get("headers")),
+                remote_addr: remote_addr.clone(),
+                content_type: post_data
+                    .get("mimeType")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            };
+            bodies.extend(
+                har_body(post_data, hex)
+                    .into_iter()
+                    .map(|body| (body, meta.clone())),
+            );
+        }
+
+        let response = entry.get("response").unwrap_or(&Value::Null);
+        if let Some(content) = response.get("content").filter(|_| !from_dbug) {
+            // Responses keep the request line, with the response headers and content type
+            let meta = RequestMeta {
+                method: method.to_string(),
+                path,
+                query,
+                headers: name_values(response.get("headers")),
+                remote_addr,
+                content_type: content
+                    .get("mimeType")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            };
+            bodies.extend(
+                har_body(content, false)
+                    .into_iter()
+                    .map(|body| (body, meta.clone())),
+            );
+        }
+
+        payloads.extend(bodies.into_iter().map(|((kind, mut value), meta)| {
+            let annotations = envelope::extract(&mut value, &meta.headers);
+            let incoming = Incoming {
+                kind,
+                value,
+                channel: channel.to_string(),
+                request: Some(meta),
+                annotations,
+            };
+            (incoming, received_at)
+        }));
+    }
+
+    payloads
+}
+
+/// Turns one array element or NDJSON line into a payload. Records written by our
+/// JSON and NDJSON exports keep their kind, request and receive time; anything
+/// else is imported as a JSON payload received now.
+fn item_payload(mut value: Value, channel: &str, now: DateTime<Utc>) -> (Incoming, DateTime<Utc>) {
+    let is_record = value.as_object().is_some_and(|object| {
+        ["id", "value", "size", "received_at"]
+            .iter()
+            .all(|key| object.contains_key(*key))
+    });
+
+    if is_record {
+        match serde_json::from_value::<Payload>(value.clone()) {
+            Ok(payload) => {
+                let incoming = Incoming {
+                    kind: payload.kind,
+                    value: payload.value,
+                    channel: channel.to_string(),
+                    request: payload.request,
+                    annotations: payload.annotations,
+                };
+                return (incoming, payload.received_at);
+            }
+            Err(e) => eprintln!("WARN: Importing malformed payload record as plain JSON: {e}"),
+        }
+    }
+
+    let annotations = envelope::extract(&mut value, &[]);
+    let incoming = Incoming {
+        kind: PayloadKind::Json,
+        value,
+        channel: channel.to_string(),
+        request: None,
+        annotations,
+    };
+    (incoming, now)
+}
+
+/// Parses an import file: a HAR archive, a JSON array, a single JSON document or
+/// NDJSON, returning each payload with the time it was originally received
+pub fn parse(contents: &str, channel: &str) -> io::Result<Vec<(Incoming, DateTime<Utc>)>> {
+    let now = Utc::now();
+
+    let items = match serde_json::from_str::<Value>(contents) {
+        Ok(Value::Object(object))
+            if object
+                .get("log")
+                .and_then(|log| log.get("entries"))
+                .is_some() =>
+        {
+            let entries = object["log"]["entries"]
+                .as_array()
+                .ok_or_else(|| invalid("HAR 'log.entries' is not an array".to_string()))?;
+            return Ok(har_payloads(entries, channel, now));
+        }
+        Ok(Value::Array(items)) => items,
+        Ok(value) => vec![value],
+        Err(e) => {
+            let lines = contents
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .collect::<Vec<_>>();
+            if lines.len() < 2 {
+                return Err(invalid(format!("Not JSON, NDJSON or HAR: {e}")));
+            }
+            lines
+                .into_iter()
+                .map(|(index, line)| {
+                    serde_json::from_str::<Value>(line)
+                        .map_err(|e| invalid(format!("Invalid JSON on line {}: {e}", index + 1)))
+                })
+                .collect::<io::Result<Vec<_>>>()?
+        }
+    };
+
+    Ok(items
+        .into_iter()
+        .map(|item| item_payload(item, channel, now))
+        .collect())
+}
+
+/// Reads and parses the import file at `path`
+pub fn read_file(path: &Path, channel: &str) -> io::Result<Vec<(Incoming, DateTime<Utc>)>> {
+    let contents = std::fs::read_to_string(path)?;
+    parse(&contents, channel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn values(payloads: &[(Incoming, DateTime<Utc>)]) -> Vec<&Value> {
+        payloads
+            .iter()
+            .map(|(incoming, _)| &incoming.value)
+            .collect()
+    }
+
+    #[test]
+    fn base64_accepts_standard_and_url_safe_alphabets() {
+        assert_eq!(
+            decode_base64("aGVsbG8=").as_deref(),
+            Some(b"hello".as_slice())
+        );
+        assert_eq!(
+            decode_base64("aGVs\nbG8").as_deref(),
+            Some(b"hello".as_slice())
+        );
+        assert_eq!(
+            decode_base64("+/8=").as_deref(),
+            Some([0xfb, 0xff].as_slice())
+        );
+        assert_eq!(
+            decode_base64("-_8=").as_deref(),
+            Some([0xfb, 0xff].as_slice())
+        );
+        assert_eq!(decode_base64("").as_deref(), Some(b"".as_slice()));
+        assert_eq!(decode_base64("aGV*"), None);
+    }
+
+    #[test]
+    fn split_url_keeps_the_path_and_query() {
+        assert_eq!(
+            split_url("https://example.com:8080/api/orders?id=1&x=2#top"),
+            ("/api/orders".to_string(), Some("id=1&x=2".to_string()))
+        );
+        assert_eq!(split_url("http://example.com"), ("/".to_string(), None));
+        assert_eq!(
+            split_url("/local/path?x"),
+            ("/local/path".to_string(), Some("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn target_channel_falls_back_to_the_file_name() {
+        let path = Path::new("/tmp/checkout.har");
+        assert_eq!(target_channel(path, Some(" billing ")), "billing");
+        assert_eq!(target_channel(path, Some("  ")), "checkout");
+        assert_eq!(target_channel(path, None), "checkout");
+        assert_eq!(target_channel(Path::new("/"), None), DEFAULT_IMPORT_CHANNEL);
+    }
+
+    #[test]
+    fn har_entries_yield_request_and_response_bodies() {
+        let har = json!({
+            "log": {
+                "entries": [
+                    {
+                        "startedDateTime": "2024-06-10T08:00:00.000Z",
+                        "serverIPAddress": "10.0.0.2",
+                        "request": {
+                            "method": "POST",
+                            "url": "https://api.example.com/orders?draft=1",
+                            "headers": [{ "name": "X-Trace", "value": "abc" }],
+                            "postData": { "mimeType": "application/json", "text": "{\"sku\": 1}" }
+                        },
+                        "response": {
+                            "headers": [],
+                            "content": {
+                                "mimeType": "text/plain",
+                                "encoding": "base64",
+                                "text": "b2s="
+                            }
+                        }
+                    },
+                    {
+                        "request": {
+                            "url": "/form",
+                            "postData": {
+                                "mimeType": "application/x-www-form-urlencoded",
+                                "params": [{ "name": "q", "value": "shoes" }]
+                            }
+                        },
+                        "response": { "content": { "mimeType": "application/json" } }
+                    }
+                ]
+            }
+        });
+        let now = Utc::now();
+        let payloads = parse(&har.to_string(), "imported").unwrap();
+
+        assert_eq!(
+            values(&payloads),
+            [&json!({ "sku": 1 }), &json!("ok"), &json!({ "q": "shoes" })]
+        );
+        let kinds = payloads
+            .iter()
+            .map(|(incoming, _)| incoming.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [PayloadKind::Json, PayloadKind::Text, PayloadKind::Form]
+        );
+
+        let (first, received_at) = &payloads[0];
+        assert_eq!(received_at.to_rfc3339(), "2024-06-10T08:00:00+00:00");
+        assert_eq!(first.channel, "imported");
+        let request = first.request.as_ref().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/orders");
+        assert_eq!(request.query.as_deref(), Some("draft=1"));
+        assert_eq!(
+            request.headers,
+            [("X-Trace".to_string(), "abc".to_string())]
+        );
+        assert_eq!(request.remote_addr.as_deref(), Some("10.0.0.2"));
+
+        // Without a start time the entry counts as received during the import
+        assert!(payloads[2].1 >= now);
+        assert_eq!(payloads[2].0.request.as_ref().unwrap().method, "GET");
+    }
+
+    #[test]
+    fn har_from_our_export_skips_responses_and_decodes_hex() {
+        let har = json!({
+            "log": {
+                "entries": [{
+                    "comment": "dbug payload 1718000000000-0000",
+                    "request": {
+                        "method": "POST",
+                        "url": "http://127.0.0.1:53821/files",
+                        "postData": {
+                            "mimeType": "application/octet-stream",
+                            "text": "ff00",
+                            "comment": "Body is hex encoded"
+                        }
+                    },
+                    "response": {
+                        "content": { "mimeType": "application/json", "text": "{\"id\": \"1718000000000-0000\"}" }
+                    }
+                }]
+            }
+        });
+        let payloads = parse(&har.to_string(), "files").unwrap();
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(payloads[0].0.kind, PayloadKind::Binary);
+        assert_eq!(payloads[0].0.value, json!("ff00"));
+    }
+
+    #[test]
+    fn json_array_is_one_payload_per_element_and_an_object_is_one() {
+        let payloads = parse(r#"[{"a": 1}, [2], "three"]"#, "c").unwrap();
+        assert_eq!(
+            values(&payloads),
+            [&json!({ "a": 1 }), &json!([2]), &json!("three")]
+        );
+
+        let payloads = parse(r#"{"a": 1}"#, "c").unwrap();
+        assert_eq!(values(&payloads), [&json!({ "a": 1 })]);
+    }
+
+    #[test]
+    fn ndjson_skips_blank_lines_and_rejects_bad_ones() {
+        let payloads = parse("{\"a\": 1}\n\n  \n{\"b\": 2}\n", "c").unwrap();
+        assert_eq!(values(&payloads), [&json!({ "a": 1 }), &json!({ "b": 2 })]);
+
+        let error = parse("{\"a\": 1}\nnot json\n", "c").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 2"), "{error}");
+
+        assert!(parse("not json", "c").is_err());
+    }
+
+    #[test]
+    fn exported_records_keep_their_kind_and_receive_time() {
+        let mut payload = Payload::from_legacy("1718000000000".to_string(), json!("hello"), 7);
+        payload.kind = PayloadKind::Text;
+        let record = serde_json::to_value(&payload).unwrap();
+
+        let payloads = parse(&Value::Array(vec![record]).to_string(), "c").unwrap();
+        let (incoming, received_at) = &payloads[0];
+        assert_eq!(incoming.kind, PayloadKind::Text);
+        assert_eq!(incoming.value, json!("hello"));
+        assert_eq!(*received_at, payload.received_at);
+    }
+}
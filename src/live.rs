@@ -0,0 +1,174 @@
+use crate::access::AllowedOrigins;
+use crate::api;
+use crate::payload::Payload;
+use crate::storage::Storage;
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use std::convert::Infallible;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use warp::filters::ws::{Message, WebSocket, Ws};
+use warp::sse::Event;
+use warp::Filter;
+
+/// Query accepted by both live endpoints, such as `/events?channel=billing`
+#[derive(Debug, Default, Deserialize)]
+struct LiveQuery {
+    /// Only payloads stored in this channel are sent
+    channel: Option<String>,
+    /// Set to include the stored request headers, which may hold credentials
+    headers: Option<String>,
+}
+
+impl LiveQuery {
+    fn matches(&self, payload: &Payload) -> bool {
+        self.channel
+            .as_deref()
+            .is_none_or(|channel| payload.channel == channel)
+    }
+}
+
+/// Waits for the next stored payload the subscriber asked for, or `None` once storage is gone.
+/// Request headers are left out unless asked for, as in the query API.
+/// A subscriber too slow to keep up skips what it missed rather than being disconnected.
+async fn next_payload(receiver: &mut broadcast::Receiver<Payload>, query: &LiveQuery) -> Option<Payload> {
+    loop {
+        match receiver.recv().await {
+            Ok(payload) if query.matches(&payload) => {
+                return Some(api::redact(payload, api::wants_headers(query.headers.as_deref())));
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(missed)) => {
+                eprintln!("WARN: Live subscriber fell behind and missed {missed} payloads");
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Sends each stored payload to a WebSocket client as `{"event": "payload", "payload": {...}}`
+/// until either side goes away. Messages from the client are ignored.
+async fn websocket_client(socket: WebSocket, mut receiver: broadcast::Receiver<Payload>, query: LiveQuery) {
+    let (mut outgoing, mut incoming) = socket.split();
+
+    loop {
+        tokio::select! {
+            payload = next_payload(&mut receiver, &query) => {
+                let Some(payload) = payload else {
+                    break;
+                };
+                let message = json!({ "event": "payload", "payload": payload }).to_string();
+                if outgoing.send(Message::text(message)).await.is_err() {
+                    break;
+                }
+            }
+            message = incoming.next() => match message {
+                Some(Ok(message)) if !message.is_close() => {}
+                _ => break,
+            },
+        }
+    }
+    let _ = outgoing.close().await;
+}
+
+/// `GET /ws` and `GET /events`, streaming every payload stored from the moment
+/// a client connects, with its id and metadata, over WebSocket and Server-Sent Events.
+/// Web pages may only connect from `origins`; browsers do not apply CORS to WebSockets.
+pub fn routes(
+    storage: Storage,
+    origins: &AllowedOrigins,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let query = warp::query::<LiveQuery>().or(warp::any().map(LiveQuery::default)).unify();
+
+    let websocket = warp::path!("ws")
+        .and(origins.gate())
+        .and(warp::ws())
+        .and(query.clone())
+        .map({
+            let storage = storage.clone();
+            move |ws: Ws, query: LiveQuery| {
+                let receiver = storage.subscribe();
+                ws.on_upgrade(move |socket| websocket_client(socket, receiver, query))
+            }
+        });
+
+    let events = warp::get()
+        .and(warp::path!("events"))
+        .and(origins.gate())
+        .and(query)
+        .map(move |query: LiveQuery| {
+            let events = futures::stream::unfold(
+                (storage.subscribe(), query),
+                |(mut receiver, query)| async move {
+                    let payload = next_payload(&mut receiver, &query).await?;
+                    let event = Event::default()
+                        .event("payload")
+                        .id(payload.id.clone())
+                        .json_data(&payload)
+                        .unwrap_or_else(|_| Event::default().comment("unserializable payload"));
+                    Some((Ok::<_, Infallible>(event), (receiver, query)))
+                },
+            );
+            warp::sse::reply(warp::sse::keep_alive().stream(events))
+        });
+
+    websocket.or(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::RequestMeta;
+    use serde_json::json;
+
+    fn payload(id: &str, channel: &str) -> Payload {
+        let mut payload = Payload::from_legacy(id.to_string(), json!({}), 2);
+        payload.channel = channel.to_string();
+        payload.request = Some(RequestMeta {
+            method: "POST".to_string(),
+            path: format!("/{channel}"),
+            headers: vec![("authorization".to_string(), "Bearer secret".to_string())],
+            ..RequestMeta::default()
+        });
+        payload
+    }
+
+    fn headers(payload: &Payload) -> usize {
+        payload.request.as_ref().map_or(0, |request| request.headers.len())
+    }
+
+    #[tokio::test]
+    async fn live_payloads_leave_out_headers_unless_asked() {
+        let (sender, mut receiver) = broadcast::channel(4);
+        sender.send(payload("1", "default")).unwrap();
+        sender.send(payload("2", "default")).unwrap();
+
+        let sent = next_payload(&mut receiver, &LiveQuery::default()).await.unwrap();
+        assert_eq!(sent.id, "1");
+        assert_eq!(headers(&sent), 0);
+
+        let query = LiveQuery {
+            headers: Some("true".to_string()),
+            ..LiveQuery::default()
+        };
+        let sent = next_payload(&mut receiver, &query).await.unwrap();
+        assert_eq!(sent.id, "2");
+        assert_eq!(headers(&sent), 1);
+    }
+
+    #[tokio::test]
+    async fn live_payloads_skip_other_channels() {
+        let (sender, mut receiver) = broadcast::channel(4);
+        sender.send(payload("1", "billing")).unwrap();
+        sender.send(payload("2", "orders")).unwrap();
+        drop(sender);
+
+        let query = LiveQuery {
+            channel: Some("orders".to_string()),
+            ..LiveQuery::default()
+        };
+        assert_eq!(next_payload(&mut receiver, &query).await.unwrap().id, "2");
+        assert!(next_payload(&mut receiver, &query).await.is_none());
+    }
+}
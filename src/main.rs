@@ -1,11 +1,43 @@
+mod access;
+mod api;
 mod app;
+mod body;
+mod cli;
+mod clipboard;
 mod components;
+mod diff;
+mod editor;
+mod envelope;
+mod export;
 mod gui;
+mod headless;
+mod import;
+mod live;
+mod payload;
+mod search;
 mod server;
 mod settings;
 mod storage;
 
 pub fn main() -> Result<(), Box<dyn std::error::Error>> {
-    gui::gui()?;
+    let args = match cli::Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            // Display rather than Debug, so the usage text is readable
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+    let result: Result<(), Box<dyn std::error::Error>> = match args.command.clone() {
+        cli::Command::Gui => gui::gui(args),
+        cli::Command::Serve => headless::serve(&args).map_err(Into::into),
+        cli::Command::Export(format) => headless::export(&args, format).map_err(Into::into),
+        cli::Command::Import(file) => headless::import(&args, &file).map_err(Into::into),
+    };
+    if let Err(e) = result {
+        // Display as well, so errors such as a store in use by another process read plainly
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    }
     Ok(())
 }
@@ -0,0 +1,242 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Channel for payloads posted to `/` and for payloads stored before channels existed
+pub const DEFAULT_CHANNEL: &str = "default";
+
+fn default_channel() -> String {
+    DEFAULT_CHANNEL.to_string()
+}
+
+/// Derives the channel from a request path: the first segment, so `POST /billing`
+/// and `POST /billing/invoice` both land in `billing`
+pub fn channel_from_path(path: &str) -> String {
+    path.split('/')
+        .find(|segment| !segment.is_empty())
+        .map_or_else(default_channel, str::to_string)
+}
+
+/// How a payload body was interpreted when it was received
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadKind {
+    /// A JSON document, stored as-is
+    #[default]
+    Json,
+    /// A UTF-8 text body, stored as a JSON string
+    Text,
+    /// URL-encoded or multipart form fields, stored as an object of field names to values
+    Form,
+    /// Arbitrary bytes, stored as a lowercase hex string
+    Binary,
+}
+
+impl PayloadKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Text => "text",
+            Self::Form => "form",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+/// Encodes bytes as a lowercase hex string, the storage format of binary payloads
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decodes a hex string produced by [`encode_hex`], returning `None` if it is malformed
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Details of the HTTP request a payload arrived in
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestMeta {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub remote_addr: Option<String>,
+    pub content_type: Option<String>,
+}
+
+impl RequestMeta {
+    /// One-line summary such as `POST /path?a=1 from 127.0.0.1:5000 (application/json)`
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} {}", self.method, self.path);
+        if let Some(query) = self.query.as_deref().filter(|q| !q.is_empty()) {
+            summary.push('?');
+            summary.push_str(query);
+        }
+        if let Some(remote_addr) = &self.remote_addr {
+            summary.push_str(&format!(" from {remote_addr}"));
+        }
+        if let Some(content_type) = &self.content_type {
+            summary.push_str(&format!(" ({content_type})"));
+        }
+        summary
+    }
+}
+
+/// How serious a sender marked a payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Severity {
+    /// Parses the names senders commonly use, such as `warning` or `fatal`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "info" | "debug" | "notice" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warn),
+            "error" | "err" | "fatal" | "critical" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Source location of the code that sent a payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Origin {
+    pub file: String,
+    pub line: Option<u32>,
+}
+
+impl Origin {
+    /// Parses `file` or `file:line`; the file itself may contain colons, as in `C:\app.php:12`
+    pub fn parse(location: &str) -> Option<Self> {
+        let location = location.trim();
+        if location.is_empty() {
+            return None;
+        }
+        match location.rsplit_once(':') {
+            Some((file, line)) if !file.is_empty() && line.parse::<u32>().is_ok() => Some(Self {
+                file: file.to_string(),
+                line: line.parse().ok(),
+            }),
+            _ => Some(Self {
+                file: location.to_string(),
+                line: None,
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}", self.file),
+            None => write!(f, "{}", self.file),
+        }
+    }
+}
+
+/// How the sender asked for a payload to be presented, taken from its envelope
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Annotations {
+    pub label: Option<String>,
+    /// A color name such as `red`, or a hex color such as `#ff8800`
+    pub color: Option<String>,
+    pub severity: Option<Severity>,
+    pub origin: Option<Origin>,
+    pub tags: Vec<String>,
+}
+
+/// A decoded payload on its way into storage, before it is assigned an id
+#[derive(Debug, Clone)]
+pub struct Incoming {
+    pub kind: PayloadKind,
+    pub value: Value,
+    pub channel: String,
+    pub request: Option<RequestMeta>,
+    pub annotations: Annotations,
+}
+
+/// A stored payload together with everything known about how it arrived
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Payload {
+    pub id: String,
+    pub value: Value,
+    pub size: u64,
+    /// Missing for payloads stored before non-JSON bodies were accepted
+    #[serde(default)]
+    pub kind: PayloadKind,
+    #[serde(default = "default_channel")]
+    pub channel: String,
+    pub received_at: DateTime<Utc>,
+    /// Missing for payloads migrated from the legacy tuple formats
+    #[serde(default)]
+    pub request: Option<RequestMeta>,
+    /// Pinned payloads are never evicted by the retention policy
+    #[serde(default)]
+    pub pinned: bool,
+    /// Label, color, severity, origin and tags sent along with the payload
+    #[serde(default)]
+    pub annotations: Annotations,
+}
+
+impl Payload {
+    /// Builds a record for a payload migrated from a legacy `(id, value)` entry,
+    /// recovering the receive time from the millisecond timestamp id
+    pub fn from_legacy(id: String, value: Value, size: u64) -> Self {
+        let received_at = id
+            .parse::<i64>()
+            .ok()
+            .and_then(DateTime::<Utc>::from_timestamp_millis)
+            .unwrap_or_else(Utc::now);
+
+        Self {
+            id,
+            value,
+            size,
+            kind: PayloadKind::Json,
+            channel: default_channel(),
+            received_at,
+            request: None,
+            pinned: false,
+            annotations: Annotations::default(),
+        }
+    }
+
+    /// Short single-line representation used for collapsed rows
+    pub fn preview(&self) -> String {
+        match (self.kind, &self.value) {
+            (PayloadKind::Text, Value::String(text)) => {
+                text.lines().next().unwrap_or_default().to_string()
+            }
+            (PayloadKind::Binary, Value::String(hex)) => format!("<{} bytes>", hex.len() / 2),
+            (PayloadKind::Form, Value::Object(fields)) => fields
+                .iter()
+                .map(|(name, value)| match value {
+                    Value::String(value) => format!("{name}={value}"),
+                    value => format!("{name}={value}"),
+                })
+                .collect::<Vec<_>>()
+                .join("&"),
+            (_, value) => format!("{value}"),
+        }
+    }
+}
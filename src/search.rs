@@ -0,0 +1,637 @@
+use crate::payload::Payload;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+
+/// One step of a JSON path such as `$.users[0].name`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// Comparison applied to the values a path selects
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl Operator {
+    // Longer operators first so `>=` is not read as `>`
+    const ALL: [(&'static str, Operator); 7] = [
+        ("==", Operator::Eq),
+        ("!=", Operator::Ne),
+        ("<=", Operator::Le),
+        (">=", Operator::Ge),
+        ("~=", Operator::Contains),
+        ("<", Operator::Lt),
+        (">", Operator::Gt),
+    ];
+}
+
+/// Error for queries that look like JSON paths but cannot be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError(String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A parsed search bar query
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchQuery {
+    /// Case-insensitive substring match against any key or scalar value
+    Text(String),
+    /// `key=value`: any object key named `key` whose value reads as `value`
+    KeyValue { key: String, value: String },
+    /// `$.a.b == 42` or jq-style `.a.b == 42`; without a comparison the path must exist
+    Path {
+        path: Vec<Segment>,
+        comparison: Option<(Operator, Value)>,
+    },
+}
+
+/// Renders a scalar the way it reads in the JSON view, without quotes around strings
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Null => Some("null".to_string()),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+fn parse_path(input: &str) -> Result<(Vec<Segment>, &str), QueryError> {
+    let mut rest = input.strip_prefix('$').unwrap_or(input);
+    let mut segments = Vec::new();
+
+    loop {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot
+                .find(|c: char| c == '.' || c == '[' || c.is_whitespace() || "=!<>~".contains(c))
+                .unwrap_or(after_dot.len());
+            let key = &after_dot[..end];
+            match key {
+                // A lone `.` is jq's identity and selects the whole payload
+                "" if segments.is_empty() => {}
+                "" => return Err(QueryError("Empty key in path".to_string())),
+                "*" => segments.push(Segment::Wildcard),
+                key => segments.push(Segment::Key(key.to_string())),
+            }
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let inner = after_bracket.trim_start();
+            if let Some(quote) = inner.chars().next().filter(|c| matches!(c, '"' | '\'')) {
+                let (key, after_key) = parse_quoted_key(&inner[1..], quote)?;
+                rest = after_key
+                    .trim_start()
+                    .strip_prefix(']')
+                    .ok_or_else(|| QueryError(format!("Expected ']' after the key '{key}' in path")))?;
+                segments.push(Segment::Key(key));
+                continue;
+            }
+
+            let end = after_bracket
+                .find(']')
+                .ok_or_else(|| QueryError("Unclosed '[' in path".to_string()))?;
+            let inner = after_bracket[..end].trim();
+            let segment = if inner == "*" {
+                Segment::Wildcard
+            } else if let Ok(index) = inner.parse::<usize>() {
+                Segment::Index(index)
+            } else {
+                return Err(QueryError(format!("Invalid index '[{inner}]' in path")));
+            };
+            segments.push(segment);
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Ok((segments, rest));
+        }
+    }
+}
+
+/// Reads a bracketed key up to its closing `quote`, where a backslash escapes the next
+/// character, returning the key and the input after the quote
+fn parse_quoted_key(input: &str, quote: char) -> Result<(String, &str), QueryError> {
+    let mut key = String::new();
+    let mut chars = input.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, escaped)) => key.push(escaped),
+                None => break,
+            },
+            c if c == quote => return Ok((key, &input[index + c.len_utf8()..])),
+            c => key.push(c),
+        }
+    }
+    Err(QueryError(format!("Unclosed {quote} in path")))
+}
+
+/// Writes a path in the syntax the search bar accepts, quoting keys that cannot
+/// follow a `.`, so a copied or remembered path parses back to the same segments
+pub fn format_path(path: &[Segment]) -> String {
+    let mut formatted = "$".to_string();
+    for segment in path {
+        match segment {
+            Segment::Key(key)
+                if !key.is_empty()
+                    && key != "*"
+                    && !key.contains(|c: char| c.is_whitespace() || ".[]'\"=!<>~".contains(c)) =>
+            {
+                formatted.push('.');
+                formatted.push_str(key);
+            }
+            Segment::Key(key) => {
+                // Single quotes read better around keys holding double quotes
+                let quote = if key.contains('"') && !key.contains('\'') { '\'' } else { '"' };
+                formatted.push('[');
+                formatted.push(quote);
+                for c in key.chars() {
+                    if c == quote || c == '\\' {
+                        formatted.push('\\');
+                    }
+                    formatted.push(c);
+                }
+                formatted.push(quote);
+                formatted.push(']');
+            }
+            Segment::Index(index) => formatted.push_str(&format!("[{index}]")),
+            Segment::Wildcard => formatted.push_str("[*]"),
+        }
+    }
+    formatted
+}
+
+fn parse_literal(input: &str) -> Value {
+    serde_json::from_str(input).unwrap_or_else(|_| {
+        // Allow unquoted and single-quoted strings for convenience
+        let unquoted = input
+            .strip_prefix('\'')
+            .and_then(|s| s.strip_suffix('\''))
+            .unwrap_or(input);
+        Value::String(unquoted.to_string())
+    })
+}
+
+impl SearchQuery {
+    /// Parses search bar input, returning `Ok(None)` for an empty query
+    pub fn parse(input: &str) -> Result<Option<Self>, QueryError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+
+        if input.starts_with('$') || input.starts_with('.') {
+            let (path, rest) = parse_path(input)?;
+            let rest = rest.trim();
+            let comparison = if rest.is_empty() {
+                None
+            } else {
+                let (op_text, operator) = Operator::ALL
+                    .into_iter()
+                    .find(|(op_text, _)| rest.starts_with(op_text))
+                    .ok_or_else(|| QueryError(format!("Expected a comparison, found '{rest}'")))?;
+                let literal = rest[op_text.len()..].trim();
+                if literal.is_empty() {
+                    return Err(QueryError(format!("Missing value after '{op_text}'")));
+                }
+                Some((operator, parse_literal(literal)))
+            };
+            return Ok(Some(SearchQuery::Path { path, comparison }));
+        }
+
+        if let Some((key, value)) = input.split_once('=') {
+            let key = key.trim();
+            if !key.is_empty() && !key.contains(char::is_whitespace) {
+                return Ok(Some(SearchQuery::KeyValue {
+                    key: key.to_string(),
+                    value: value.trim().to_string(),
+                }));
+            }
+        }
+
+        Ok(Some(SearchQuery::Text(input.to_lowercase())))
+    }
+
+    /// Whether a payload body matches the query
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            SearchQuery::Text(needle) => contains_text(value, needle),
+            SearchQuery::KeyValue {
+                key,
+                value: expected,
+            } => contains_key_value(value, key, expected),
+            SearchQuery::Path { .. } => !self.path_hits(value).is_empty(),
+        }
+    }
+
+    /// JSON pointers of the nodes a path query matches in `value`: the nodes its
+    /// path selects that are not null and satisfy its comparison. Empty for other queries.
+    pub fn path_hits(&self, value: &Value) -> HashSet<String> {
+        let SearchQuery::Path { path, comparison } = self else {
+            return HashSet::new();
+        };
+
+        let mut selected = Vec::new();
+        select(value, path, &mut String::new(), &mut selected);
+        selected
+            .into_iter()
+            .filter(|(_, value)| match comparison {
+                None => !value.is_null(),
+                Some((operator, literal)) => compare(value, *operator, literal),
+            })
+            .map(|(pointer, _)| pointer)
+            .collect()
+    }
+
+    /// Whether `value` matches, with the path hits to highlight, or `None` when it does not.
+    /// Runs a path query once for both, where `matches` and `path_hits` would each run it.
+    pub fn hits(&self, value: &Value) -> Option<HashSet<String>> {
+        match self {
+            SearchQuery::Path { .. } => Some(self.path_hits(value)).filter(|hits| !hits.is_empty()),
+            _ => self.matches(value).then(HashSet::new),
+        }
+    }
+
+    /// Whether a token rendered in the JSON view should be highlighted.
+    /// `is_key` is true for object keys; string tokens are passed without quotes.
+    /// Path queries highlight whole nodes instead, see [`SearchQuery::path_hits`].
+    pub fn highlights(&self, token: &str, is_key: bool) -> bool {
+        match self {
+            SearchQuery::Text(needle) => token.to_lowercase().contains(needle.as_str()),
+            SearchQuery::KeyValue { key, value } => {
+                if is_key {
+                    token == key
+                } else {
+                    token == value
+                }
+            }
+            SearchQuery::Path { .. } => false,
+        }
+    }
+}
+
+/// Payloads in `channel` (every channel when `None`) matching `query`, in their original order
+pub fn filter_payloads<'a>(
+    payloads: &'a [Payload],
+    channel: Option<&str>,
+    query: Option<&SearchQuery>,
+) -> Vec<&'a Payload> {
+    payloads
+        .iter()
+        .filter(|payload| channel.is_none_or(|channel| payload.channel == channel))
+        .filter(|payload| query.is_none_or(|query| query.matches(&payload.value)))
+        .collect()
+}
+
+fn contains_text(value: &Value, needle: &str) -> bool {
+    match value {
+        Value::Object(map) => map.iter().any(|(key, value)| {
+            key.to_lowercase().contains(needle) || contains_text(value, needle)
+        }),
+        Value::Array(items) => items.iter().any(|item| contains_text(item, needle)),
+        scalar => scalar_text(scalar).is_some_and(|text| text.to_lowercase().contains(needle)),
+    }
+}
+
+fn contains_key_value(value: &Value, key: &str, expected: &str) -> bool {
+    match value {
+        Value::Object(map) => map.iter().any(|(k, v)| {
+            (k == key && scalar_text(v).is_some_and(|text| text == expected))
+                || contains_key_value(v, key, expected)
+        }),
+        Value::Array(items) => items
+            .iter()
+            .any(|item| contains_key_value(item, key, expected)),
+        _ => false,
+    }
+}
+
+/// Escapes an object key or array index for use as one RFC 6901 JSON pointer token
+pub fn pointer_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Converts a JSON pointer into path segments, using `value` to tell array indexes from keys
+pub fn path_from_pointer(value: &Value, pointer: &str) -> Option<Vec<Segment>> {
+    let mut segments = Vec::new();
+    if pointer.is_empty() {
+        return Some(segments);
+    }
+
+    let mut node = value;
+    for token in pointer.strip_prefix('/')?.split('/') {
+        let key = token.replace("~1", "/").replace("~0", "~");
+        node = match node {
+            Value::Object(map) => {
+                let child = map.get(&key)?;
+                segments.push(Segment::Key(key));
+                child
+            }
+            Value::Array(items) => {
+                let index = key.parse::<usize>().ok()?;
+                segments.push(Segment::Index(index));
+                items.get(index)?
+            }
+            _ => return None,
+        };
+    }
+    Some(segments)
+}
+
+/// Collects the nodes `path` selects, with their JSON pointers
+fn select<'a>(
+    value: &'a Value,
+    path: &[Segment],
+    pointer: &mut String,
+    selected: &mut Vec<(String, &'a Value)>,
+) {
+    let Some((segment, rest)) = path.split_first() else {
+        selected.push((pointer.clone(), value));
+        return;
+    };
+
+    let mut visit = |token: &str, child: &'a Value, pointer: &mut String| {
+        let len = pointer.len();
+        pointer.push('/');
+        pointer.push_str(&pointer_token(token));
+        select(child, rest, pointer, selected);
+        pointer.truncate(len);
+    };
+
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(map)) => {
+            if let Some(child) = map.get(key) {
+                visit(key, child, pointer);
+            }
+        }
+        (Segment::Index(index), Value::Array(items)) => {
+            if let Some(child) = items.get(*index) {
+                visit(&index.to_string(), child, pointer);
+            }
+        }
+        (Segment::Wildcard, Value::Object(map)) => {
+            for (key, child) in map {
+                visit(key, child, pointer);
+            }
+        }
+        (Segment::Wildcard, Value::Array(items)) => {
+            for (index, child) in items.iter().enumerate() {
+                visit(&index.to_string(), child, pointer);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn compare(value: &Value, operator: Operator, literal: &Value) -> bool {
+    if operator == Operator::Contains {
+        let needle = scalar_text(literal)
+            .unwrap_or_else(|| literal.to_string())
+            .to_lowercase();
+        return match value {
+            Value::Array(items) => items.iter().any(|item| item == literal),
+            value => scalar_text(value).is_some_and(|text| text.to_lowercase().contains(&needle)),
+        };
+    }
+
+    let ordering = match (value, literal) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .zip(b.as_f64())
+            .and_then(|(a, b)| a.partial_cmp(&b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        // Numbers sent as strings still compare against numeric literals
+        (Value::String(a), Value::Number(b)) => a
+            .parse::<f64>()
+            .ok()
+            .zip(b.as_f64())
+            .and_then(|(a, b)| a.partial_cmp(&b)),
+        (a, b) if a == b => Some(Ordering::Equal),
+        _ => None,
+    };
+
+    match operator {
+        Operator::Eq => ordering == Some(Ordering::Equal),
+        Operator::Ne => ordering != Some(Ordering::Equal),
+        Operator::Lt => ordering == Some(Ordering::Less),
+        Operator::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Operator::Gt => ordering == Some(Ordering::Greater),
+        Operator::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        Operator::Contains => unreachable!("handled above"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(input: &str) -> SearchQuery {
+        SearchQuery::parse(input).unwrap().unwrap()
+    }
+
+    fn hits(input: &str, value: &Value) -> Vec<String> {
+        let mut hits = parse(input)
+            .path_hits(value)
+            .into_iter()
+            .collect::<Vec<_>>();
+        hits.sort();
+        hits
+    }
+
+    #[test]
+    fn parse_tells_the_query_kinds_apart() {
+        assert_eq!(SearchQuery::parse("   "), Ok(None));
+        assert_eq!(
+            parse("  Timeout "),
+            SearchQuery::Text("timeout".to_string())
+        );
+        assert_eq!(
+            parse("status = failed"),
+            SearchQuery::KeyValue {
+                key: "status".to_string(),
+                value: "failed".to_string(),
+            }
+        );
+        // A key with spaces is not a key=value query
+        assert_eq!(parse("a b=c"), SearchQuery::Text("a b=c".to_string()));
+        assert_eq!(
+            parse("$.users[0]['full name']"),
+            SearchQuery::Path {
+                path: vec![
+                    Segment::Key("users".to_string()),
+                    Segment::Index(0),
+                    Segment::Key("full name".to_string()),
+                ],
+                comparison: None,
+            }
+        );
+        assert_eq!(
+            parse(".items[*].price >= 10"),
+            SearchQuery::Path {
+                path: vec![
+                    Segment::Key("items".to_string()),
+                    Segment::Wildcard,
+                    Segment::Key("price".to_string()),
+                ],
+                comparison: Some((Operator::Ge, json!(10))),
+            }
+        );
+        assert_eq!(
+            parse("."),
+            SearchQuery::Path {
+                path: Vec::new(),
+                comparison: None,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_paths() {
+        assert!(SearchQuery::parse("$.a[0").is_err());
+        assert!(SearchQuery::parse("$.a[x]").is_err());
+        assert!(SearchQuery::parse("$.a..b").is_err());
+        assert!(SearchQuery::parse("$.a ==").is_err());
+        assert!(SearchQuery::parse("$.a foo").is_err());
+    }
+
+    #[test]
+    fn text_matches_keys_and_scalars_ignoring_case() {
+        let value = json!({ "User": { "tags": ["Admin", 42, null] } });
+        assert!(parse("user").matches(&value));
+        assert!(parse("ADMIN").matches(&value));
+        assert!(parse("42").matches(&value));
+        assert!(parse("null").matches(&value));
+        assert!(!parse("guest").matches(&value));
+        assert!(parse("adm").highlights("Admin", false));
+    }
+
+    #[test]
+    fn key_value_matches_nested_scalars_exactly() {
+        let value = json!({ "orders": [{ "status": "failed", "total": 12.5 }] });
+        assert!(parse("status=failed").matches(&value));
+        assert!(parse("total=12.5").matches(&value));
+        assert!(!parse("status=fail").matches(&value));
+        assert!(!parse("orders=failed").matches(&value));
+        assert!(parse("status=failed").highlights("status", true));
+        assert!(!parse("status=failed").highlights("status", false));
+    }
+
+    #[test]
+    fn path_queries_select_and_compare_nodes() {
+        let value = json!({
+            "items": [
+                { "name": "pen", "price": 3 },
+                { "name": "book", "price": "12" },
+                { "name": "bag", "price": null },
+            ],
+            "a/b": { "c~d": true },
+        });
+
+        assert_eq!(
+            hits("$.items[*].price", &value),
+            ["/items/0/price", "/items/1/price"]
+        );
+        assert_eq!(hits("$.items[*].price > 5", &value), ["/items/1/price"]);
+        assert_eq!(hits("$.items[0].name == pen", &value), ["/items/0/name"]);
+        assert_eq!(
+            hits("$.items[*].name ~= 'B'", &value),
+            ["/items/1/name", "/items/2/name"]
+        );
+        assert_eq!(hits("$['a/b']['c~d']", &value), ["/a~1b/c~0d"]);
+        assert!(hits("$.missing", &value).is_empty());
+
+        assert!(parse("$.items[2].name != pen").matches(&value));
+        assert!(!parse("$.items[*].price > 100").matches(&value));
+        assert!(!parse("$.items[2].price").matches(&value));
+    }
+
+    #[test]
+    fn hits_run_the_query_once_for_matching_and_highlighting() {
+        let value = json!({ "a": [1, 2, 3] });
+        let path_hits = parse("$.a[*] >= 2").hits(&value).unwrap();
+        assert_eq!(
+            path_hits,
+            HashSet::from(["/a/1".to_string(), "/a/2".to_string()])
+        );
+        assert!(parse("$.a[*] > 5").hits(&value).is_none());
+        assert_eq!(parse("a").hits(&value), Some(HashSet::new()));
+        assert!(parse("b=1").hits(&value).is_none());
+    }
+
+    #[test]
+    fn formatted_paths_parse_back_to_the_same_segments() {
+        let keys = [
+            "plain",
+            "with space",
+            "a.b",
+            "a]b",
+            "x == 1",
+            "it's",
+            "say \"hi\"",
+            "both ' and \"",
+            r"back\slash",
+            r"trailing\",
+            "",
+            "*",
+            "~tilde/slash",
+            "日本",
+        ];
+        for key in keys {
+            let path = vec![
+                Segment::Key(key.to_string()),
+                Segment::Index(3),
+                Segment::Wildcard,
+            ];
+            let formatted = format_path(&path);
+            assert_eq!(
+                SearchQuery::parse(&formatted),
+                Ok(Some(SearchQuery::Path {
+                    path,
+                    comparison: None
+                })),
+                "{formatted}"
+            );
+        }
+        assert_eq!(
+            format_path(&[Segment::Key("a]b".to_string())]),
+            r#"$["a]b"]"#
+        );
+        assert_eq!(
+            format_path(&[Segment::Key("say \"hi\"".to_string())]),
+            r#"$['say "hi"']"#
+        );
+        assert_eq!(
+            format_path(&[Segment::Key("both ' and \"".to_string())]),
+            r#"$["both ' and \""]"#
+        );
+    }
+
+    #[test]
+    fn pointers_convert_back_to_paths() {
+        let value = json!({ "users": [{ "a/b": 1 }] });
+        assert_eq!(
+            path_from_pointer(&value, "/users/0/a~1b"),
+            Some(vec![
+                Segment::Key("users".to_string()),
+                Segment::Index(0),
+                Segment::Key("a/b".to_string()),
+            ])
+        );
+        assert_eq!(path_from_pointer(&value, ""), Some(Vec::new()));
+        assert_eq!(path_from_pointer(&value, "/users/1"), None);
+    }
+}
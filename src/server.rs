@@ -1,49 +1,431 @@
-use iced::futures::Stream;
+use iced::futures::{SinkExt, Stream};
 use iced::stream;
-use serde_json::Value;
-use warp::{hyper::Method, Filter};
-use iced::futures::SinkExt;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use warp::http::{HeaderMap, StatusCode};
+use warp::path::FullPath;
+use warp::reply::{Reply, Response};
+use warp::{hyper::Method, Filter, Rejection};
+
+use crate::access::{AllowedOrigins, ForbiddenOrigin};
+use crate::api;
+use crate::body;
+use crate::cli::Args;
+use crate::envelope;
+use crate::live;
+use crate::payload::{channel_from_path, Incoming, PayloadKind, RequestMeta};
+use crate::settings::Settings;
+use crate::storage::{AddError, Added, Storage};
+
+// Largest request body read into memory, checked before it is read (64 MiB)
+const MAX_BODY_BYTES: u64 = 64 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
 pub enum ServerMessage {
-    PayloadReceived(Value),
+    /// A payload the server stored
+    PayloadReceived(Added),
+    /// Payloads were deleted through the API, so cached lists are stale
+    PayloadsDeleted,
+    Listening(SocketAddr),
+    BindFailed(String),
+}
+
+/// Current state of the payload server, as reported by its stream
+#[derive(Debug, Clone, Default)]
+pub enum ServerStatus {
+    #[default]
+    Starting,
+    Listening(SocketAddr),
+    Failed(String),
+}
+
+/// Host and port the payload server binds to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ListenAddress {
+    pub host: String,
+    pub port: u16,
+}
+
+impl ListenAddress {
+    /// Resolves the active address: CLI flags win over env vars, which win over settings
+    pub fn resolve(settings: &Settings, args: &Args) -> Self {
+        let host = args
+            .host
+            .clone()
+            .or_else(|| {
+                std::env::var("DBUG_HOST")
+                    .ok()
+                    .filter(|host| !host.is_empty())
+            })
+            .unwrap_or_else(|| settings.listen_host().to_string());
+
+        let port = args
+            .port
+            .or_else(|| {
+                let value = std::env::var("DBUG_PORT").ok()?;
+                value
+                    .parse::<u16>()
+                    .inspect_err(|e| eprintln!("WARN: Ignoring invalid DBUG_PORT '{value}': {e}"))
+                    .ok()
+            })
+            .unwrap_or_else(|| settings.listen_port());
+
+        Self { host, port }
+    }
 }
 
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// What the payload server runs with: the address to bind, the storage its live endpoints
+/// follow and the web origins allowed to read stored payloads
+#[derive(Clone)]
+pub struct ServerConfig {
+    pub address: ListenAddress,
+    pub storage: Storage,
+    pub origins: AllowedOrigins,
+}
+
+// The address and origins identify a running server, so changing either restarts the server
+impl PartialEq for ServerConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address && self.origins == other.origins
+    }
+}
 
-pub(crate) enum _ServerInput {
-    DoSomeWork,
+impl Eq for ServerConfig {}
+
+impl Hash for ServerConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address.hash(state);
+        self.origins.hash(state);
+    }
+}
+
+/// Collects the request details stored alongside each payload
+fn request_meta(
+    method: &Method,
+    path: &FullPath,
+    query: String,
+    headers: &HeaderMap,
+    remote_addr: Option<SocketAddr>,
+) -> RequestMeta {
+    RequestMeta {
+        method: method.to_string(),
+        path: path.as_str().to_string(),
+        query: Some(query).filter(|query| !query.is_empty()),
+        headers: headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect(),
+        remote_addr: remote_addr.map(|addr| addr.to_string()),
+        content_type: headers
+            .get(warp::http::header::CONTENT_TYPE)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned()),
+    }
 }
 
+/// Rejection for request bodies that could not be read
+#[derive(Debug)]
+struct InvalidBody(String);
 
- pub fn listen() -> impl Stream<Item = ServerMessage> {
+impl warp::reject::Reject for InvalidBody {}
+
+/// Error reply for a payload that could not be stored: 413 when it exceeds the retention limits
+fn add_error_reply(e: &AddError) -> Response {
+    match e {
+        AddError::TooLarge { size, limit } => warp::reply::with_status(
+            warp::reply::json(&json!({ "error": e.to_string(), "size": size, "limit": limit })),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )
+        .into_response(),
+        AddError::Io(_) => {
+            eprintln!("ERROR: Failed to store payload: {e}");
+            api::error_reply(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to store payload: {e}"),
+            )
+        }
+    }
+}
+
+/// Stores the payloads of one request and forwards them to the app. The reply describes
+/// the stored payload, or `{"payloads": [...]}` when the body held several, along with a
+/// `warning` when a body declared as JSON was stored as something else.
+async fn store(
+    storage: Storage,
+    mut output: futures::channel::mpsc::Sender<ServerMessage>,
+    meta: RequestMeta,
+    bodies: Vec<(PayloadKind, Value)>,
+    warning: Option<String>,
+) -> Response {
+    // An empty or blank body holds nothing to store; say so rather than reply with an empty list
+    if bodies.is_empty() {
+        return api::error_reply(StatusCode::BAD_REQUEST, "Request body holds no payload");
+    }
+
+    let channel = channel_from_path(&meta.path);
+    let incoming = bodies
+        .into_iter()
+        .map(|(kind, mut value)| {
+            let annotations = envelope::extract(&mut value, &meta.headers);
+            Incoming {
+                kind,
+                value,
+                channel: channel.clone(),
+                request: Some(meta.clone()),
+                annotations,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Nothing is stored when any payload could never be kept
+    for item in &incoming {
+        if let Err(e) = storage.check_size(item) {
+            return add_error_reply(&e);
+        }
+    }
+
+    let mut stored = Vec::with_capacity(incoming.len());
+    for item in incoming {
+        let added = match storage.add_json(item) {
+            Ok(added) => added,
+            Err(e) => return add_error_reply(&e),
+        };
+        stored.push(json!({
+            "id": added.payload.id,
+            "channel": added.payload.channel,
+            "kind": added.payload.kind,
+            "size": added.payload.size,
+            "evicted": added.evicted.len(),
+        }));
+        let _ = output.send(ServerMessage::PayloadReceived(added)).await;
+    }
+
+    let mut reply = match <[Value; 1]>::try_from(stored) {
+        Ok([single]) => single,
+        Err(stored) => json!({ "payloads": stored }),
+    };
+    if let Some(warning) = warning {
+        reply["warning"] = Value::String(warning);
+    }
+    warp::reply::json(&reply).into_response()
+}
+
+/// Turns rejections into JSON error bodies, so clients can report what went wrong
+async fn rejection_reply(rejection: Rejection) -> Result<Response, Infallible> {
+    let (status, message) = if let Some(InvalidBody(e)) = rejection.find::<InvalidBody>() {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid request body: {e}"),
+        )
+    } else if let Some(ForbiddenOrigin(origin)) = rejection.find::<ForbiddenOrigin>() {
+        (
+            StatusCode::FORBIDDEN,
+            format!(
+                "Pages on {origin} may not read payloads; add it to allowed_origins in config.json"
+            ),
+        )
+    } else if let Some(e) = rejection.find::<warp::cors::CorsForbidden>() {
+        (StatusCode::FORBIDDEN, e.to_string())
+    } else if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, "Not found".to_string())
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method not allowed".to_string(),
+        )
+    } else if rejection.find::<warp::reject::PayloadTooLarge>().is_some() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request body too large; the limit is {MAX_BODY_BYTES} bytes"),
+        )
+    } else if rejection.find::<warp::reject::LengthRequired>().is_some() {
+        (
+            StatusCode::LENGTH_REQUIRED,
+            "Request body needs a Content-Length header".to_string(),
+        )
+    } else {
+        eprintln!("WARN: Unhandled rejection: {rejection:?}");
+        (StatusCode::BAD_REQUEST, format!("{rejection:?}"))
+    };
+    Ok(api::error_reply(status, message))
+}
+
+pub fn listen(config: &ServerConfig) -> impl Stream<Item = ServerMessage> {
+    let ServerConfig {
+        address,
+        storage,
+        origins,
+    } = config.clone();
+
+    stream::channel(
+        100,
+        move |mut output: futures::channel::mpsc::Sender<ServerMessage>| async move {
+            let request = warp::method()
+                .and(warp::path::full())
+                .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+                .and(warp::header::headers_cloned())
+                .and(warp::addr::remote())
+                .map(
+                    |method: Method,
+                     path: FullPath,
+                     query: String,
+                     headers: HeaderMap,
+                     remote_addr: Option<SocketAddr>| {
+                        request_meta(&method, &path, query, &headers, remote_addr)
+                    },
+                );
+
+            // Multipart bodies need their boundary-aware parser; everything else is decoded from raw bytes
+            let multipart = request
+                .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+                .and(warp::multipart::form().max_length(MAX_BODY_BYTES))
+                .and_then(|meta: RequestMeta, form| async move {
+                    let value = body::decode_multipart(form).await.map_err(|e| {
+                        eprintln!("WARN: Failed to read multipart body: {e}");
+                        warp::reject::custom(InvalidBody(e.to_string()))
+                    })?;
+                    Ok::<_, warp::Rejection>((meta, vec![(PayloadKind::Form, value)], None))
+                })
+                .untuple_one();
+
+            let raw = request
+                .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+                .and(warp::body::bytes())
+                .map(|meta: RequestMeta, bytes: warp::hyper::body::Bytes| {
+                    let bodies = body::decode(meta.content_type.as_deref(), &bytes);
+                    // Declared JSON that did not parse was kept as text or binary; tell the sender why
+                    let stored_as = bodies
+                        .iter()
+                        .map(|(kind, _)| *kind)
+                        .find(|kind| *kind != PayloadKind::Json);
+                    let warning = stored_as.and_then(|kind| {
+                        body::json_error(meta.content_type.as_deref(), &bytes).map(|e| {
+                            format!("Body is not valid JSON ({e}), stored as {}", kind.as_str())
+                        })
+                    });
+                    (meta, bodies, warning)
+                })
+                .untuple_one();
+
+            let payload = warp::post().and(multipart.or(raw).unify()).then({
+                let storage = storage.clone();
+                let output = output.clone();
+                move |meta: RequestMeta,
+                      bodies: Vec<(PayloadKind, Value)>,
+                      warning: Option<String>| {
+                    store(storage.clone(), output.clone(), meta, bodies, warning)
+                }
+            });
+
+            // Any page may send payloads, but only allowed origins may read or delete them
+            let ingest_cors = warp::cors()
+                .allow_any_origin()
+                .allow_methods(&[Method::POST, Method::OPTIONS])
+                .allow_headers(vec![
+                    "Content-Type",
+                    "Authorization",
+                    "Accept",
+                    "Origin",
+                    "X-Requested-With",
+                ])
+                .max_age(3600);
+
+            let routes = live::routes(storage.clone(), &origins)
+                .or(api::routes(storage, output.clone(), &origins))
+                .with(origins.cors())
+                .or(payload.with(ingest_cors))
+                .recover(rejection_reply);
+
+            let socket_addr =
+                match tokio::net::lookup_host((address.host.as_str(), address.port)).await {
+                    Ok(mut addrs) => addrs.next(),
+                    Err(e) => {
+                        let error = format!("Could not resolve {address}: {e}");
+                        eprintln!("ERROR: {error}");
+                        let _ = output.send(ServerMessage::BindFailed(error)).await;
+                        return;
+                    }
+                };
+
+            let Some(socket_addr) = socket_addr else {
+                let error = format!("Could not resolve {address}: no addresses found");
+                eprintln!("ERROR: {error}");
+                let _ = output.send(ServerMessage::BindFailed(error)).await;
+                return;
+            };
+
+            match warp::serve(routes).try_bind_ephemeral(socket_addr) {
+                Ok((bound, server)) => {
+                    // stderr, so `serve` keeps stdout for payloads
+                    eprintln!("INFO: Server started at http://{bound}");
+                    let _ = output.send(ServerMessage::Listening(bound)).await;
+                    server.await;
+                }
+                Err(e) => {
+                    let error = format!("Could not listen on {address}: {e}");
+                    eprintln!("ERROR: {error}");
+                    let _ = output.send(ServerMessage::BindFailed(error)).await;
+                }
+            }
+        },
+    )
+}
 
-     stream::channel(100, |output: futures::channel::mpsc::Sender<ServerMessage>| async move {
-         let payload = warp::post()
-             .and(warp::body::json())
-             .map({
-                 let output = output.clone();
-                 move |body: Value| {
-                     let mut output_clone = output.clone();
-                     tokio::task::spawn(async move {
-                         let _ = output_clone.send(ServerMessage::PayloadReceived(body)).await;
-                     });
-                     "Hello!".to_string()
-                 }
-             });
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn args(host: Option<&str>, port: Option<u16>) -> Args {
+        Args {
+            host: host.map(str::to_string),
+            port,
+            ..Args::default()
+        }
+    }
 
+    // Environment variables are process-wide, so every case that sets them runs in this one test
+    #[test]
+    fn flags_win_over_env_vars_which_win_over_settings() {
+        let mut settings = Settings::default();
+        settings.set_listen_address("10.0.0.1".to_string(), 4000);
+        std::env::remove_var("DBUG_HOST");
+        std::env::remove_var("DBUG_PORT");
 
-         let cors = warp::cors()
-             .allow_any_origin()
-             .allow_methods(&[Method::POST, Method::OPTIONS])
-             .allow_headers(vec!["Content-Type", "Authorization", "Accept", "Origin", "X-Requested-With"])
-             .max_age(3600);
+        let address = ListenAddress::resolve(&settings, &args(None, None));
+        assert_eq!(address.to_string(), "10.0.0.1:4000");
 
-         let routes = payload.with(cors);
+        std::env::set_var("DBUG_HOST", "0.0.0.0");
+        std::env::set_var("DBUG_PORT", "5000");
+        let address = ListenAddress::resolve(&settings, &args(None, None));
+        assert_eq!(address.to_string(), "0.0.0.0:5000");
 
-         println!("Server started at http://127.0.0.1:53821");
+        let address = ListenAddress::resolve(&settings, &args(Some("::1"), Some(6000)));
+        assert_eq!(address.to_string(), "[::1]:6000");
 
-         warp::serve(routes).run(([127, 0, 0, 1], 53821)).await;
+        // Blank or invalid variables fall back to the settings
+        std::env::set_var("DBUG_HOST", "");
+        std::env::set_var("DBUG_PORT", "http");
+        let address = ListenAddress::resolve(&settings, &args(None, None));
+        assert_eq!(address.to_string(), "10.0.0.1:4000");
 
-     })
+        std::env::remove_var("DBUG_HOST");
+        std::env::remove_var("DBUG_PORT");
+    }
 }
@@ -1,6 +1,93 @@
+use crate::editor::DEFAULT_EDITOR_COMMAND;
 use crate::storage::Storage;
+use chrono::TimeDelta;
 use iced::{Point, Size, Theme};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const DEFAULT_LISTEN_HOST: &str = "127.0.0.1";
+pub const DEFAULT_LISTEN_PORT: u16 = 53821;
+
+fn default_listen_host() -> String {
+    DEFAULT_LISTEN_HOST.to_string()
+}
+
+fn default_listen_port() -> u16 {
+    DEFAULT_LISTEN_PORT
+}
+
+fn default_editor_command() -> String {
+    DEFAULT_EDITOR_COMMAND.to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Total payload size kept when no retention has been configured (2 MiB)
+pub const DEFAULT_RETENTION_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Size and count limits for the payloads of a single channel
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelLimits {
+    pub max_bytes: Option<u64>,
+    pub max_count: Option<usize>,
+}
+
+impl ChannelLimits {
+    pub fn exceeded(&self, count: usize, bytes: u64) -> bool {
+        self.max_count.is_some_and(|max| count > max) || self.max_bytes.is_some_and(|max| bytes > max)
+    }
+}
+
+/// Which payloads to keep. When a limit is exceeded the oldest unpinned payloads
+/// are evicted first; `None` means unlimited.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Retention {
+    pub max_bytes: Option<u64>,
+    pub max_count: Option<usize>,
+    pub max_age_secs: Option<u64>,
+    /// Limits applied to individual channels on top of the global ones
+    pub channels: BTreeMap<String, ChannelLimits>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_bytes: Some(DEFAULT_RETENTION_BYTES),
+            max_count: None,
+            max_age_secs: None,
+            channels: BTreeMap::new(),
+        }
+    }
+}
+
+impl Retention {
+    pub fn exceeded(&self, count: usize, bytes: u64) -> bool {
+        self.max_count.is_some_and(|max| count > max) || self.max_bytes.is_some_and(|max| bytes > max)
+    }
+
+    pub fn max_age(&self) -> Option<TimeDelta> {
+        self.max_age_secs
+            .and_then(|secs| TimeDelta::try_seconds(i64::try_from(secs).ok()?))
+    }
+}
+
+/// Which payloads are shown expanded as they arrive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Expansion {
+    /// The newest payload; the one expanded this way before it collapses, while payloads
+    /// expanded by hand stay open
+    #[default]
+    ExpandNewest,
+    /// Every payload, keeping earlier ones open
+    ExpandAll,
+    /// None; payloads are expanded by hand
+    CollapseAll,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SerializablePoint {
@@ -49,6 +136,29 @@ pub struct Settings {
     theme_name: String,
     window_position: SerializablePoint,
     window_size: SerializableSize,
+    #[serde(default = "default_listen_host")]
+    listen_host: String,
+    #[serde(default = "default_listen_port")]
+    listen_port: u16,
+    #[serde(default)]
+    retention: Retention,
+    #[serde(default)]
+    expansion: Expansion,
+    /// Scroll back to the newest payload when one arrives
+    #[serde(default = "default_true")]
+    follow_tail: bool,
+    /// Collapsing a node in the JSON view collapses it in every payload of the same shape
+    #[serde(default)]
+    remember_collapsed_paths: bool,
+    /// JSONPaths collapsed in every payload, with array indexes written as `[*]`
+    #[serde(default)]
+    collapsed_paths: BTreeSet<String>,
+    /// Command opening a payload's origin, with `{file}` and `{line}` placeholders
+    #[serde(default = "default_editor_command")]
+    editor_command: String,
+    /// Web origins, such as `http://localhost:5173`, whose pages may use the query API and live stream
+    #[serde(default)]
+    allowed_origins: Vec<String>,
     // ... any other settings
 }
 
@@ -61,6 +171,15 @@ impl Default for Settings {
                 width: 1280.0,
                 height: 800.0,
             },
+            listen_host: default_listen_host(),
+            listen_port: default_listen_port(),
+            retention: Retention::default(),
+            expansion: Expansion::default(),
+            follow_tail: true,
+            remember_collapsed_paths: false,
+            collapsed_paths: BTreeSet::new(),
+            editor_command: default_editor_command(),
+            allowed_origins: Vec::new(),
         }
     }
 }
@@ -107,4 +226,78 @@ impl Settings {
     pub fn get_window_size(&self) -> Size {
         self.window_size.clone().into()
     }
+
+    pub fn listen_host(&self) -> &str {
+        &self.listen_host
+    }
+
+    pub fn listen_port(&self) -> u16 {
+        self.listen_port
+    }
+
+    pub fn set_listen_address(&mut self, host: String, port: u16) {
+        self.listen_host = host;
+        self.listen_port = port;
+    }
+
+    pub fn retention(&self) -> &Retention {
+        &self.retention
+    }
+
+    pub fn set_retention(&mut self, retention: Retention) {
+        self.retention = retention;
+    }
+
+    pub fn expansion(&self) -> Expansion {
+        self.expansion
+    }
+
+    pub fn set_expansion(&mut self, expansion: Expansion) {
+        self.expansion = expansion;
+    }
+
+    pub fn follow_tail(&self) -> bool {
+        self.follow_tail
+    }
+
+    pub fn set_follow_tail(&mut self, follow: bool) {
+        self.follow_tail = follow;
+    }
+
+    pub fn remember_collapsed_paths(&self) -> bool {
+        self.remember_collapsed_paths
+    }
+
+    pub fn set_remember_collapsed_paths(&mut self, remember: bool) {
+        self.remember_collapsed_paths = remember;
+    }
+
+    pub fn collapsed_paths(&self) -> &BTreeSet<String> {
+        &self.collapsed_paths
+    }
+
+    /// Adds or removes a remembered collapsed path
+    pub fn set_path_collapsed(&mut self, path: String, collapsed: bool) {
+        if collapsed {
+            self.collapsed_paths.insert(path);
+        } else {
+            self.collapsed_paths.remove(&path);
+        }
+    }
+
+    pub fn clear_collapsed_paths(&mut self) {
+        self.collapsed_paths.clear();
+    }
+
+    pub fn editor_command(&self) -> &str {
+        &self.editor_command
+    }
+
+    pub fn allowed_origins(&self) -> &[String] {
+        &self.allowed_origins
+    }
+
+    pub fn set_editor_command(&mut self, command: String) {
+        self.editor_command = command;
+    }
 }
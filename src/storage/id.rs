@@ -0,0 +1,186 @@
+use crate::payload::Payload;
+use std::collections::HashSet;
+
+/// Largest sequence number within one millisecond; the id moves on to the next millisecond after it
+const MAX_SEQUENCE: u32 = 9999;
+
+/// Formats a payload id as zero-padded milliseconds and a sequence number, such as
+/// `1718000000000-0001`, so ids sort chronologically as plain strings
+fn format_id(millis: i64, sequence: u32) -> String {
+    format!("{millis:013}-{sequence:04}")
+}
+
+/// Splits an id in the current format into its milliseconds and sequence number
+fn parse_id(id: &str) -> Option<(i64, u32)> {
+    let (millis, sequence) = id.split_once('-')?;
+    if millis.len() != 13 || sequence.len() != 4 {
+        return None;
+    }
+    Some((millis.parse().ok()?, sequence.parse().ok()?))
+}
+
+/// Whether `id` is in the current format rather than a bare millisecond timestamp
+pub fn is_current(id: &str) -> bool {
+    parse_id(id).is_some()
+}
+
+/// Hands out ids that are unique and never go backwards, even when several payloads
+/// arrive in the same millisecond or the clock is turned back
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IdGenerator {
+    last: Option<(i64, u32)>,
+}
+
+impl IdGenerator {
+    /// Makes sure later ids sort after `id`
+    pub fn observe(&mut self, id: &str) {
+        if let Some(parsed) = parse_id(id) {
+            self.last = self.last.max(Some(parsed));
+        }
+    }
+
+    /// The id for a payload received at `millis`
+    pub fn next(&mut self, millis: i64) -> String {
+        let next = match self.last {
+            Some((last, sequence)) if millis <= last && sequence < MAX_SEQUENCE => (last, sequence + 1),
+            Some((last, _)) if millis <= last => (last + 1, 0),
+            _ => (millis, 0),
+        };
+        self.last = Some(next);
+        format_id(next.0, next.1)
+    }
+}
+
+/// The first id for a payload received at `millis` that is not in `used`.
+/// Used for payloads stored at their original time, such as imports.
+pub fn unused_id(millis: i64, used: &HashSet<String>) -> String {
+    let (mut millis, mut sequence) = (millis, 0);
+    loop {
+        let id = format_id(millis, sequence);
+        if !used.contains(&id) {
+            return id;
+        }
+        if sequence < MAX_SEQUENCE {
+            sequence += 1;
+        } else {
+            (millis, sequence) = (millis + 1, 0);
+        }
+    }
+}
+
+/// Gives payloads stored with millisecond ids, which collide when several arrive
+/// in the same millisecond, unique ids derived from their receive time
+pub fn migrate(payloads: &mut [Payload]) {
+    let mut used = payloads
+        .iter()
+        .filter(|payload| is_current(&payload.id))
+        .map(|payload| payload.id.clone())
+        .collect::<HashSet<_>>();
+
+    for payload in payloads.iter_mut().filter(|payload| !is_current(&payload.id)) {
+        let id = unused_id(payload.received_at.timestamp_millis(), &used);
+        used.insert(id.clone());
+        payload.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn legacy(id: &str) -> Payload {
+        Payload::from_legacy(id.to_string(), json!({}), 2)
+    }
+
+    #[test]
+    fn ids_sort_chronologically_as_strings() {
+        assert_eq!(format_id(1_718_000_000_000, 1), "1718000000000-0001");
+        assert!(format_id(999, MAX_SEQUENCE) < format_id(1000, 0));
+        assert!(format_id(1000, 9) < format_id(1000, 10));
+    }
+
+    #[test]
+    fn current_ids_are_recognized() {
+        assert!(is_current("1718000000000-0001"));
+        assert!(!is_current("1718000000000"));
+        assert!(!is_current("1718000000000-1"));
+        assert!(!is_current("abc-0001"));
+    }
+
+    #[test]
+    fn next_counts_up_within_a_millisecond() {
+        let mut ids = IdGenerator::default();
+        assert_eq!(ids.next(1000), format_id(1000, 0));
+        assert_eq!(ids.next(1000), format_id(1000, 1));
+        assert_eq!(ids.next(1001), format_id(1001, 0));
+    }
+
+    #[test]
+    fn next_never_goes_backwards_when_the_clock_does() {
+        let mut ids = IdGenerator::default();
+        assert_eq!(ids.next(2000), format_id(2000, 0));
+        assert_eq!(ids.next(1500), format_id(2000, 1));
+    }
+
+    #[test]
+    fn next_moves_to_the_next_millisecond_after_the_last_sequence() {
+        let mut ids = IdGenerator::default();
+        ids.observe(&format_id(1000, MAX_SEQUENCE - 1));
+        assert_eq!(ids.next(1000), format_id(1000, MAX_SEQUENCE));
+        assert_eq!(ids.next(1000), format_id(1001, 0));
+        assert_eq!(ids.next(1001), format_id(1001, 1));
+    }
+
+    #[test]
+    fn observe_keeps_the_largest_current_id() {
+        let mut ids = IdGenerator::default();
+        ids.observe(&format_id(3000, 5));
+        ids.observe(&format_id(1000, 7));
+        ids.observe("9999999999999");
+        assert_eq!(ids.next(0), format_id(3000, 6));
+    }
+
+    #[test]
+    fn unused_id_skips_ids_in_use_and_rolls_over() {
+        let used = (0..=MAX_SEQUENCE)
+            .map(|sequence| format_id(1000, sequence))
+            .chain([format_id(1001, 0)])
+            .collect::<HashSet<_>>();
+        assert_eq!(unused_id(1000, &used), format_id(1001, 1));
+        assert_eq!(unused_id(2000, &used), format_id(2000, 0));
+    }
+
+    #[test]
+    fn migrate_gives_colliding_legacy_ids_unique_current_ids() {
+        let mut payloads = [
+            legacy("1000"),
+            legacy("1000"),
+            legacy(&format_id(1000, 0)),
+            legacy("1001"),
+        ];
+        migrate(&mut payloads);
+
+        let ids = payloads
+            .iter()
+            .map(|payload| payload.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                format_id(1000, 1).as_str(),
+                format_id(1000, 2).as_str(),
+                format_id(1000, 0).as_str(),
+                format_id(1001, 0).as_str(),
+            ]
+        );
+    }
+
+    #[test]
+    fn migrate_leaves_current_ids_alone() {
+        let mut payloads = [legacy(&format_id(1000, 3)), legacy(&format_id(1000, 4))];
+        let before = payloads.clone();
+        migrate(&mut payloads);
+        assert_eq!(payloads, before);
+    }
+}
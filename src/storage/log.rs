@@ -0,0 +1,543 @@
+use crate::payload::Payload;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// Start a new segment once the active one grows past this size (8 MiB)
+const MAX_SEGMENT_BYTES: u64 = 8 * 1024 * 1024;
+
+// Never compact logs smaller than this, however much of them is garbage (4 MiB)
+const MIN_COMPACTION_BYTES: u64 = 4 * 1024 * 1024;
+
+// Held exclusively by the one process writing the log
+const LOCK_FILE: &str = "lock";
+
+const SEGMENT_PREFIX: &str = "segment-";
+const SEGMENT_SUFFIX: &str = ".ndjson";
+
+/// One line of the log. Payloads are never rewritten in place: deletes are
+/// recorded as tombstones and dropped for good when the log is compacted.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(super) enum Record {
+    Put { payload: Box<Payload> },
+    Delete { id: String },
+    Clear,
+}
+
+/// Writes `contents` to `path` so that readers see either the old or the new
+/// file, never a partial one: write a sibling temp file, sync it, then rename.
+pub(super) fn atomic_write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+fn segment_path(dir: &Path, index: u64) -> PathBuf {
+    dir.join(format!("{SEGMENT_PREFIX}{index:08}{SEGMENT_SUFFIX}"))
+}
+
+/// Lists the segment indices in `dir`, oldest first
+fn segment_indices(dir: &Path) -> io::Result<Vec<u64>> {
+    let mut indices = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            name.strip_prefix(SEGMENT_PREFIX)?
+                .strip_suffix(SEGMENT_SUFFIX)?
+                .parse::<u64>()
+                .ok()
+        })
+        .collect::<Vec<_>>();
+    indices.sort_unstable();
+    Ok(indices)
+}
+
+/// Reads every record of a segment. A line that fails to parse at the very end
+/// of the newest segment is a write torn by a crash; with `repair` it is truncated away.
+fn read_segment(path: &Path, repair: bool) -> io::Result<Vec<(Record, u64)>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    let mut valid_len: u64 = 0;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+
+        match serde_json::from_str::<Record>(line.trim_end()) {
+            Ok(record) if line.ends_with('\n') => {
+                records.push((record, read as u64));
+                valid_len += read as u64;
+            }
+            _ if line.trim().is_empty() => valid_len += read as u64,
+            result => {
+                let at_end = reader.fill_buf()?.is_empty();
+                if repair && at_end {
+                    eprintln!(
+                        "WARN: Truncating incomplete record at the end of {path:?}, left by an interrupted write"
+                    );
+                    OpenOptions::new()
+                        .write(true)
+                        .open(path)?
+                        .set_len(valid_len)?;
+                    break;
+                }
+                let reason = result
+                    .err()
+                    .map_or_else(|| "missing newline".to_string(), |e| e.to_string());
+                eprintln!("WARN: Skipping unreadable record in {path:?}: {reason}");
+                valid_len += read as u64;
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+/// Replays records in order: later puts replace earlier ones, deletes and clears drop them.
+/// `observe` sees every record with its size in bytes.
+fn replay(records: Vec<(Record, u64)>, mut observe: impl FnMut(&Record, u64)) -> Vec<Payload> {
+    let mut payloads: Vec<Option<Payload>> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (record, len) in records {
+        observe(&record, len);
+        match record {
+            Record::Put { payload } => match positions.get(&payload.id) {
+                Some(&index) => payloads[index] = Some(*payload),
+                None => {
+                    positions.insert(payload.id.clone(), payloads.len());
+                    payloads.push(Some(*payload));
+                }
+            },
+            Record::Delete { id } => {
+                if let Some(index) = positions.remove(&id) {
+                    payloads[index] = None;
+                }
+            }
+            Record::Clear => {
+                payloads.clear();
+                positions.clear();
+            }
+        }
+    }
+    payloads.into_iter().flatten().collect()
+}
+
+/// Takes the exclusive lock on the log in `dir`. A second writer would append to
+/// segments that the first one's compaction deletes, losing payloads.
+fn lock(dir: &Path) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(LOCK_FILE))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            format!(
+                "The payload store in {} is in use by another dbug process (the app, `serve` or `import`); close it first",
+                dir.display()
+            ),
+        )),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// Append-only, segmented log of payload records on disk
+pub(super) struct Log {
+    dir: PathBuf,
+    /// Released when the log is dropped
+    _lock: File,
+    active: File,
+    active_index: u64,
+    active_bytes: u64,
+    /// Bytes across all segments, live or not
+    total_bytes: u64,
+    /// Bytes taken by tombstones and by records that were deleted or overwritten
+    garbage_bytes: u64,
+    /// Size of the record holding each live payload
+    live_record_bytes: HashMap<String, u64>,
+}
+
+impl Log {
+    /// Opens the log in `dir` for writing, returning it with the live payloads it holds.
+    /// Fails when another process has it open.
+    pub(super) fn open(dir: &Path) -> io::Result<(Self, Vec<Payload>)> {
+        fs::create_dir_all(dir)?;
+        let lock = lock(dir)?;
+
+        // Leftovers from a compaction interrupted before its rename; the old segments are intact
+        for entry in fs::read_dir(dir)?.flatten() {
+            if entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == "tmp")
+            {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+
+        let indices = segment_indices(dir)?;
+        let mut records = Vec::new();
+        let mut total_bytes = 0;
+        for (position, &index) in indices.iter().enumerate() {
+            let path = segment_path(dir, index);
+            records.extend(read_segment(&path, position + 1 == indices.len())?);
+            total_bytes += fs::metadata(&path)?.len();
+        }
+
+        let active_index = indices.last().copied().unwrap_or(1);
+        let active_path = segment_path(dir, active_index);
+        let mut active = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&active_path)?;
+        let active_bytes = active.seek(SeekFrom::End(0))?;
+
+        let mut log = Self {
+            dir: dir.to_path_buf(),
+            _lock: lock,
+            active,
+            active_index,
+            active_bytes,
+            total_bytes,
+            garbage_bytes: 0,
+            live_record_bytes: HashMap::new(),
+        };
+
+        let payloads = replay(records, |record, len| log.track(record, len));
+        Ok((log, payloads))
+    }
+
+    /// Reads the live payloads in `dir` without changing anything on disk,
+    /// so it is safe while another process is writing to the log
+    pub(super) fn read(dir: &Path) -> io::Result<Vec<Payload>> {
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut records = Vec::new();
+        for index in segment_indices(dir)? {
+            match read_segment(&segment_path(dir, index), false) {
+                Ok(segment) => records.extend(segment),
+                // Removed by a compaction in the writing process since it was listed
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(replay(records, |_, _| {}))
+    }
+
+    /// Updates the live and garbage accounting for a record of `len` bytes
+    fn track(&mut self, record: &Record, len: u64) {
+        match record {
+            Record::Put { payload } => {
+                if let Some(replaced) = self.live_record_bytes.insert(payload.id.clone(), len) {
+                    self.garbage_bytes += replaced;
+                }
+            }
+            Record::Delete { id } => {
+                self.garbage_bytes += len + self.live_record_bytes.remove(id).unwrap_or(0);
+            }
+            Record::Clear => {
+                self.garbage_bytes += len + self.live_record_bytes.values().sum::<u64>();
+                self.live_record_bytes.clear();
+            }
+        }
+    }
+
+    /// Whether nothing has ever been written to the log
+    pub(super) fn is_empty(&self) -> bool {
+        self.total_bytes == 0
+    }
+
+    /// Appends records to the active segment, starting a new segment when it is full
+    pub(super) fn append(&mut self, records: &[Record]) -> io::Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut buffer = Vec::new();
+        for record in records {
+            let start = buffer.len();
+            serde_json::to_writer(&mut buffer, record)?;
+            buffer.push(b'\n');
+            self.track(record, (buffer.len() - start) as u64);
+        }
+
+        if self.active_bytes > 0 && self.active_bytes + buffer.len() as u64 > MAX_SEGMENT_BYTES {
+            self.active_index += 1;
+            self.active = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(segment_path(&self.dir, self.active_index))?;
+            self.active_bytes = 0;
+        }
+
+        // A single write per batch keeps a crash from interleaving partial records
+        self.active.write_all(&buffer)?;
+        self.active_bytes += buffer.len() as u64;
+        self.total_bytes += buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Whether enough of the log is garbage (deleted or evicted payloads) to be worth rewriting
+    pub(super) fn needs_compaction(&self) -> bool {
+        self.garbage_bytes > MIN_COMPACTION_BYTES && self.garbage_bytes * 2 > self.total_bytes
+    }
+
+    /// Rewrites the log as a single segment holding only `live` payloads. The new segment
+    /// starts with a clear, so old segments left behind by a crash or a failed removal
+    /// replay to nothing; they are garbage, removed by the next compaction.
+    pub(super) fn compact(&mut self, live: &[Payload]) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        serde_json::to_writer(&mut buffer, &Record::Clear)?;
+        buffer.push(b'\n');
+        let garbage_bytes = buffer.len() as u64;
+
+        let mut live_record_bytes = HashMap::with_capacity(live.len());
+        for payload in live {
+            let start = buffer.len();
+            serde_json::to_writer(
+                &mut buffer,
+                &Record::Put {
+                    payload: Box::new(payload.clone()),
+                },
+            )?;
+            buffer.push(b'\n');
+            live_record_bytes.insert(payload.id.clone(), (buffer.len() - start) as u64);
+        }
+
+        let old_indices = segment_indices(&self.dir)?;
+        let new_index = self.active_index + 1;
+        let new_path = segment_path(&self.dir, new_index);
+        atomic_write(&new_path, &buffer)?;
+
+        // Appends must go to the new segment from here on, or they would replay before its clear
+        self.active = match OpenOptions::new().append(true).open(&new_path) {
+            Ok(active) => active,
+            Err(e) => {
+                let _ = fs::remove_file(&new_path);
+                return Err(e);
+            }
+        };
+        self.active_index = new_index;
+        self.active_bytes = buffer.len() as u64;
+        self.total_bytes = buffer.len() as u64;
+        self.garbage_bytes = garbage_bytes;
+        self.live_record_bytes = live_record_bytes;
+
+        for index in old_indices {
+            let path = segment_path(&self.dir, index);
+            let len = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+            if let Err(e) = fs::remove_file(&path) {
+                eprintln!("WARN: Could not remove compacted segment {path:?}: {e}");
+                self.total_bytes += len;
+                self.garbage_bytes += len;
+            }
+        }
+
+        eprintln!(
+            "INFO: Compacted payload log to {} payloads ({} bytes)",
+            live.len(),
+            self.active_bytes
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::temp_dir::TempDir;
+    use serde_json::json;
+
+    fn payload(id: &str, n: u64) -> Payload {
+        Payload::from_legacy(id.to_string(), json!({ "n": n }), 8)
+    }
+
+    fn put(id: &str, n: u64) -> Record {
+        Record::Put {
+            payload: Box::new(payload(id, n)),
+        }
+    }
+
+    fn ids(payloads: &[Payload]) -> Vec<&str> {
+        payloads.iter().map(|payload| payload.id.as_str()).collect()
+    }
+
+    #[test]
+    fn new_log_is_empty() {
+        let dir = TempDir::new();
+        let (log, payloads) = Log::open(dir.path()).unwrap();
+        assert!(log.is_empty());
+        assert!(payloads.is_empty());
+        assert!(!log.needs_compaction());
+    }
+
+    #[test]
+    fn reopening_replays_puts_deletes_and_clears() {
+        let dir = TempDir::new();
+        {
+            let (mut log, _) = Log::open(dir.path()).unwrap();
+            log.append(&[put("1", 1), put("2", 1), put("3", 1)])
+                .unwrap();
+            log.append(&[Record::Delete {
+                id: "2".to_string(),
+            }])
+            .unwrap();
+        }
+        let (mut log, payloads) = Log::open(dir.path()).unwrap();
+        assert!(!log.is_empty());
+        assert_eq!(ids(&payloads), ["1", "3"]);
+
+        log.append(&[Record::Clear, put("4", 1)]).unwrap();
+        drop(log);
+        let (_, payloads) = Log::open(dir.path()).unwrap();
+        assert_eq!(ids(&payloads), ["4"]);
+    }
+
+    #[test]
+    fn later_put_replaces_payload_in_place() {
+        let dir = TempDir::new();
+        {
+            let (mut log, _) = Log::open(dir.path()).unwrap();
+            log.append(&[put("1", 1), put("2", 1), put("1", 2)])
+                .unwrap();
+        }
+        let (_, payloads) = Log::open(dir.path()).unwrap();
+        assert_eq!(payloads, [payload("1", 2), payload("2", 1)]);
+    }
+
+    #[test]
+    fn open_truncates_torn_record_at_the_end() {
+        let dir = TempDir::new();
+        {
+            let (mut log, _) = Log::open(dir.path()).unwrap();
+            log.append(&[put("1", 1)]).unwrap();
+        }
+        let path = segment_path(dir.path(), 1);
+        let valid_len = fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"op":"put","payload":{"id":"2""#)
+            .unwrap();
+        drop(file);
+
+        assert_eq!(ids(&Log::read(dir.path()).unwrap()), ["1"]);
+        assert!(fs::metadata(&path).unwrap().len() > valid_len);
+
+        let (mut log, payloads) = Log::open(dir.path()).unwrap();
+        assert_eq!(ids(&payloads), ["1"]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), valid_len);
+
+        log.append(&[put("3", 1)]).unwrap();
+        drop(log);
+        let (_, payloads) = Log::open(dir.path()).unwrap();
+        assert_eq!(ids(&payloads), ["1", "3"]);
+    }
+
+    #[test]
+    fn record_without_newline_is_torn() {
+        let dir = TempDir::new();
+        fs::create_dir_all(dir.path()).unwrap();
+        let path = segment_path(dir.path(), 1);
+        let mut contents = serde_json::to_vec(&put("1", 1)).unwrap();
+        contents.push(b'\n');
+        let valid_len = contents.len() as u64;
+        contents.extend(serde_json::to_vec(&put("2", 1)).unwrap());
+        fs::write(&path, contents).unwrap();
+
+        let records = read_segment(&path, true).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(fs::metadata(&path).unwrap().len(), valid_len);
+    }
+
+    #[test]
+    fn compaction_leaves_one_segment_with_the_live_payloads() {
+        let dir = TempDir::new();
+        let live = [payload("1", 2), payload("3", 1)];
+        {
+            let (mut log, _) = Log::open(dir.path()).unwrap();
+            log.append(&[put("1", 1), put("2", 1), put("3", 1)])
+                .unwrap();
+            log.append(&[
+                Record::Delete {
+                    id: "2".to_string(),
+                },
+                put("1", 2),
+            ])
+            .unwrap();
+            log.compact(&live).unwrap();
+            assert!(!log.needs_compaction());
+            log.append(&[put("4", 1)]).unwrap();
+        }
+
+        assert_eq!(segment_indices(dir.path()).unwrap(), [2]);
+        let (_, payloads) = Log::open(dir.path()).unwrap();
+        assert_eq!(
+            payloads,
+            [payload("1", 2), payload("3", 1), payload("4", 1)]
+        );
+    }
+
+    #[test]
+    fn old_segments_left_by_an_interrupted_compaction_replay_to_nothing() {
+        let dir = TempDir::new();
+        let live = [payload("1", 2)];
+        let old_path = segment_path(dir.path(), 1);
+        {
+            let (mut log, _) = Log::open(dir.path()).unwrap();
+            log.append(&[put("1", 1), put("2", 1), put("3", 1)])
+                .unwrap();
+            // 2 and 3 were deleted in memory, but their deletes never reached the log
+            let old_segment = fs::read(&old_path).unwrap();
+            log.compact(&live).unwrap();
+            // As if the process died before removing the old segment
+            fs::write(&old_path, old_segment).unwrap();
+        }
+
+        assert_eq!(segment_indices(dir.path()).unwrap(), [1, 2]);
+        let (mut log, payloads) = Log::open(dir.path()).unwrap();
+        assert_eq!(payloads, live);
+
+        log.compact(&payloads).unwrap();
+        assert_eq!(segment_indices(dir.path()).unwrap(), [3]);
+    }
+
+    #[test]
+    fn open_removes_leftovers_of_an_interrupted_compaction() {
+        let dir = TempDir::new();
+        {
+            let (mut log, _) = Log::open(dir.path()).unwrap();
+            log.append(&[put("1", 1)]).unwrap();
+        }
+        let leftover = segment_path(dir.path(), 2).with_extension("tmp");
+        fs::write(&leftover, b"partial").unwrap();
+
+        let (_, payloads) = Log::open(dir.path()).unwrap();
+        assert_eq!(ids(&payloads), ["1"]);
+        assert!(!leftover.exists());
+    }
+
+    #[test]
+    fn second_open_fails_while_the_log_is_held() {
+        let dir = TempDir::new();
+        let (log, _) = Log::open(dir.path()).unwrap();
+        let error = Log::open(dir.path()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+
+        assert!(Log::read(dir.path()).is_ok());
+        drop(log);
+        assert!(Log::open(dir.path()).is_ok());
+    }
+}
@@ -0,0 +1,727 @@
+use crate::payload::{Incoming, Payload};
+use crate::settings::Retention;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, oneshot};
+
+mod id;
+mod log;
+#[cfg(test)]
+mod temp_dir;
+mod writer;
+
+use id::IdGenerator;
+use log::{atomic_write, Log, Record};
+use writer::{Command, Writer};
+
+/// Stored payloads a live subscriber may fall behind by before it misses some
+const LIVE_CAPACITY: usize = 256;
+
+/// Helper function to estimate the size of a JSON value in bytes
+/// Uses compact JSON representation length as an estimate.
+fn estimate_payload_size(value: &Value) -> u64 {
+    serde_json::to_string(value).unwrap_or_default().len() as u64
+}
+
+/// Opens the payload log in `storage_dir`, moving in payloads stored by older versions
+/// and giving them current ids. The compacted log replaces everything written before it,
+/// so a crash before old segments are removed cannot bring back payloads under their old ids.
+fn open_log(storage_dir: &Path) -> io::Result<(Log, Vec<Payload>)> {
+    let (mut log, mut payloads) = Log::open(&storage_dir.join("log"))?;
+
+    // Older versions rewrote everything to data.json; move it into the log once
+    let data_file = storage_dir.join("data.json");
+    let from_legacy = log.is_empty() && data_file.exists();
+    if from_legacy {
+        payloads = load_legacy(&data_file)?;
+    }
+
+    // Older versions used millisecond ids, which collide; renumber them once
+    let needs_new_ids = payloads.iter().any(|payload| !id::is_current(&payload.id));
+    if needs_new_ids {
+        id::migrate(&mut payloads);
+        eprintln!("INFO: Gave {} payloads collision-free ids", payloads.len());
+    }
+
+    if from_legacy || needs_new_ids {
+        log.compact(&payloads)?;
+    }
+    if from_legacy {
+        fs::rename(&data_file, storage_dir.join("data.json.migrated"))?;
+    }
+    Ok((log, payloads))
+}
+
+/// Reads payloads from the data.json file written by older versions, in any of its formats
+fn load_legacy(data_file: &Path) -> io::Result<Vec<Payload>> {
+    let mut file = File::open(data_file)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    let payloads = if let Ok(parsed_data) = serde_json::from_str::<Vec<Payload>>(&contents) {
+        eprintln!(
+            "INFO: Loaded {} existing payloads, total size: {} bytes",
+            parsed_data.len(),
+            parsed_data.iter().map(|payload| payload.size).sum::<u64>()
+        );
+        parsed_data
+    } else if let Ok(old_data) = serde_json::from_str::<Vec<(String, Value)>>(&contents) {
+        old_data
+            .into_iter()
+            .map(|(id, value)| {
+                let size = estimate_payload_size(&value);
+                Payload::from_legacy(id, value, size)
+            })
+            .collect()
+    } else {
+        match serde_json::from_str::<Vec<(String, Value, u64)>>(&contents) {
+            Ok(parsed_data) => {
+                eprintln!(
+                    "INFO: Migrated {} legacy payloads, total size: {} bytes",
+                    parsed_data.len(),
+                    parsed_data.iter().map(|(_, _, size)| size).sum::<u64>()
+                );
+                parsed_data
+                    .into_iter()
+                    .map(|(id, value, size)| Payload::from_legacy(id, value, size))
+                    .collect()
+            }
+            Err(e) => {
+                eprintln!(
+                    "WARN: Failed to parse data.json (all formats), starting fresh: {e}",
+                );
+                Vec::new()
+            }
+        }
+    };
+    Ok(payloads)
+}
+
+/// Payloads held in memory, with running totals kept in sync on every change
+#[derive(Default)]
+struct StorageState {
+    payloads: Vec<Payload>,
+    total_bytes: u64,
+    /// Number of stored payloads per channel
+    channel_counts: BTreeMap<String, usize>,
+    /// Total size of the stored payloads per channel
+    channel_bytes: HashMap<String, u64>,
+    retention: Retention,
+    ids: IdGenerator,
+}
+
+impl StorageState {
+    fn new(payloads: Vec<Payload>) -> Self {
+        let mut state = Self::default();
+        for payload in payloads {
+            state.push(payload);
+        }
+        state
+    }
+
+    fn push(&mut self, payload: Payload) {
+        self.ids.observe(&payload.id);
+        self.count(&payload);
+        self.payloads.push(payload);
+    }
+
+    /// Inserts a payload after every payload received at or before it
+    fn insert_chronologically(&mut self, payload: Payload) {
+        let index = self
+            .payloads
+            .partition_point(|stored| stored.received_at <= payload.received_at);
+        self.ids.observe(&payload.id);
+        self.count(&payload);
+        self.payloads.insert(index, payload);
+    }
+
+    /// Adds a payload about to be stored to the running totals
+    fn count(&mut self, payload: &Payload) {
+        self.total_bytes += payload.size;
+        *self.channel_counts.entry(payload.channel.clone()).or_default() += 1;
+        *self.channel_bytes.entry(payload.channel.clone()).or_default() += payload.size;
+    }
+
+    fn remove(&mut self, index: usize) -> Payload {
+        let payload = self.payloads.remove(index);
+        self.total_bytes = self.total_bytes.saturating_sub(payload.size);
+        if let Some(count) = self.channel_counts.get_mut(&payload.channel) {
+            *count -= 1;
+            if *count == 0 {
+                self.channel_counts.remove(&payload.channel);
+                self.channel_bytes.remove(&payload.channel);
+            }
+        }
+        if let Some(bytes) = self.channel_bytes.get_mut(&payload.channel) {
+            *bytes = bytes.saturating_sub(payload.size);
+        }
+        payload
+    }
+
+    /// Keeps only the payloads matching `keep`, returning the removed ones
+    fn retain(&mut self, keep: impl FnMut(&Payload) -> bool) -> Vec<Payload> {
+        let (kept, removed) = std::mem::take(&mut self.payloads)
+            .into_iter()
+            .partition(keep);
+        let retention = std::mem::take(&mut self.retention);
+        // Ids of removed payloads are never handed out again
+        let ids = self.ids;
+        *self = Self::new(kept);
+        self.retention = retention;
+        self.ids = ids;
+        removed
+    }
+
+    /// Index of the oldest payload that may be evicted and matches `filter`.
+    /// Pinned payloads are never evicted, and neither is the newest payload,
+    /// so a single payload larger than the limits is still kept.
+    fn oldest_evictable(&self, filter: impl Fn(&Payload) -> bool) -> Option<usize> {
+        let newest = self.payloads.len().checked_sub(1)?;
+        self.payloads[..newest]
+            .iter()
+            .position(|payload| !payload.pinned && filter(payload))
+    }
+
+    /// Evicts the oldest unpinned payloads until the retention policy holds, returning them
+    fn enforce_retention(&mut self, now: DateTime<Utc>) -> Vec<Payload> {
+        let retention = self.retention.clone();
+        let mut evicted = Vec::new();
+
+        if let Some(max_age) = retention.max_age() {
+            let cutoff = now - max_age;
+            while let Some(index) = self.oldest_evictable(|payload| payload.received_at < cutoff) {
+                evicted.push(self.remove(index));
+            }
+        }
+
+        for (channel, limits) in &retention.channels {
+            loop {
+                let count = self.channel_counts.get(channel).copied().unwrap_or(0);
+                let bytes = self.channel_bytes.get(channel).copied().unwrap_or(0);
+                if !limits.exceeded(count, bytes) {
+                    break;
+                }
+                let Some(index) = self.oldest_evictable(|payload| &payload.channel == channel) else {
+                    break;
+                };
+                evicted.push(self.remove(index));
+            }
+        }
+
+        while retention.exceeded(self.payloads.len(), self.total_bytes) {
+            let Some(index) = self.oldest_evictable(|_| true) else {
+                break;
+            };
+            evicted.push(self.remove(index));
+        }
+
+        if !evicted.is_empty() {
+            eprintln!(
+                "INFO: Retention policy evicted {} payloads. New total: {} bytes",
+                evicted.len(),
+                self.total_bytes
+            );
+        }
+        evicted
+    }
+}
+
+/// A payload that was just stored, with the ids of older payloads evicted to make room
+#[derive(Debug, Clone)]
+pub struct Added {
+    pub payload: Payload,
+    pub evicted: Vec<String>,
+}
+
+/// Why a payload was not stored
+#[derive(Debug)]
+pub enum AddError {
+    /// Larger than the retention policy keeps for its channel, so it could never be kept
+    TooLarge { size: u64, limit: u64 },
+    Io(io::Error),
+}
+
+impl fmt::Display for AddError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddError::TooLarge { size, limit } => {
+                write!(f, "Payload of {size} bytes exceeds the retention limit of {limit} bytes")
+            }
+            AddError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<io::Error> for AddError {
+    fn from(e: io::Error) -> Self {
+        AddError::Io(e)
+    }
+}
+
+/// Outcome of an import: how many payloads were stored and how many were evicted afterwards
+#[derive(Debug, Clone, Copy)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub evicted: usize,
+}
+
+/// Storage struct to manage data persistence
+#[derive(Clone)]
+pub struct Storage {
+    data: Arc<Mutex<StorageState>>,
+    /// Writes changes to the log in the background, in the order they were applied to `data`
+    writer: Writer,
+    /// Every payload stored, for the live WebSocket and SSE endpoints
+    live: broadcast::Sender<Payload>,
+}
+
+impl Storage {
+    fn storage_dir() -> io::Result<PathBuf> {
+        dirs::home_dir()
+            .map(|mut path| {
+                path.push(".dbug_desktop");
+                path
+            })
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))
+    }
+
+    /// Creates a new Storage instance, evicting whatever `retention` no longer allows
+    pub fn new(retention: Retention) -> io::Result<Self> {
+        let storage_dir = Self::storage_dir()?;
+
+        // Inlined logic from ensure_storage_dir()
+        if !storage_dir.exists() {
+            fs::create_dir_all(&storage_dir)?;
+        }
+
+        let (log, initial_payloads) = open_log(&storage_dir)?;
+        let data = Arc::new(Mutex::new(StorageState::new(initial_payloads)));
+        let storage = Self {
+            writer: Writer::spawn(log, Arc::clone(&data))?,
+            data,
+            live: broadcast::channel(LIVE_CAPACITY).0,
+        };
+        storage.set_retention(retention)?;
+        Ok(storage)
+    }
+
+    /// Replaces the retention policy and applies it immediately, returning how many payloads were evicted
+    pub fn set_retention(&self, retention: Retention) -> io::Result<usize> {
+        match self.data.lock() {
+            Ok(mut state) => {
+                state.retention = retention;
+                let evicted = state.enforce_retention(Utc::now());
+                let tombstones = evicted
+                    .iter()
+                    .map(|payload| Record::Delete { id: payload.id.clone() })
+                    .collect::<Vec<_>>();
+                self.persist(tombstones)?;
+                Ok(evicted.len())
+            }
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in set_retention: {poisoned}");
+                Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned"))
+            }
+        }
+    }
+
+    /// Reads all stored payloads, newest first, without writing anything.
+    /// Used by CLI commands that may run alongside the GUI or `serve`.
+    pub fn read_only() -> io::Result<Vec<Payload>> {
+        let storage_dir = Self::storage_dir()?;
+        let log_dir = storage_dir.join("log");
+        let data_file = storage_dir.join("data.json");
+
+        let mut payloads = if !log_dir.exists() && data_file.exists() {
+            load_legacy(&data_file)?
+        } else {
+            Log::read(&log_dir)?
+        };
+        // Renumbered in memory only; the app rewrites the store the next time it opens it
+        id::migrate(&mut payloads);
+        payloads.reverse();
+        Ok(payloads)
+    }
+
+    pub fn config_path() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".dbug_desktop")
+            .join("config.json")
+    }
+
+    #[allow(clippy::map_unwrap_or)]
+    pub fn save_config<T: serde::Serialize>(config: &T) -> io::Result<()> {
+        let config_file = Self::config_path();
+        let fallback = PathBuf::from(".");
+        let dir = config_file.parent().unwrap_or(&fallback);
+
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("ERROR: Failed to create config directory {dir:?}: {e}");
+            return Err(e);
+        }
+
+        let contents = match serde_json::to_vec_pretty(config) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("ERROR: Failed to serialize config for {config_file:?}: {e}");
+                return Err(io::Error::new(io::ErrorKind::Other, e));
+            }
+        };
+
+        // Written atomically so a crash mid-save never leaves a truncated config
+        atomic_write(&config_file, &contents).inspect_err(|e| {
+            eprintln!("ERROR: Failed to write config file {config_file:?}: {e}");
+        })
+    }
+
+    #[allow(clippy::map_unwrap_or)] // Allow this pattern for clarity of fallback
+    pub fn load_config<T: serde::de::DeserializeOwned + Default>() -> T {
+        let config_file = dirs::home_dir()
+            .map(|mut p| {
+                p.push(".dbug_desktop");
+                p.join("config.json")
+            })
+            .unwrap_or_else(|| PathBuf::from("./.dbug_desktop/config.json"));
+
+        if let Ok(file) = File::open(&config_file) {
+            serde_json::from_reader(file).unwrap_or_else(|e| {
+                eprintln!(
+                    "WARN: Failed to parse config file {config_file:?}, using defaults: {e}"
+                );
+                T::default()
+            })
+        } else {
+            T::default()
+        }
+    }
+
+    /// Checks that a payload fits the size limits of the retention policy for its channel,
+    /// returning its size. Anything larger would evict every other payload and still not fit.
+    pub fn check_size(&self, incoming: &Incoming) -> Result<u64, AddError> {
+        let size = estimate_payload_size(&incoming.value);
+        let retention = match self.data.lock() {
+            Ok(state) => state.retention.clone(),
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in check_size: {poisoned}");
+                return Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned").into());
+            }
+        };
+
+        let limit = retention
+            .max_bytes
+            .into_iter()
+            .chain(retention.channels.get(&incoming.channel).and_then(|limits| limits.max_bytes))
+            .min();
+        match limit {
+            Some(limit) if size > limit => Err(AddError::TooLarge { size, limit }),
+            _ => Ok(size),
+        }
+    }
+
+    /// Adds a decoded payload to the storage, enforcing the retention policy
+    pub fn add_json(&self, incoming: Incoming) -> Result<Added, AddError> {
+        let new_payload_size = self.check_size(&incoming)?;
+        let received_at = Utc::now();
+
+        match self.data.lock() {
+            Ok(mut state) => {
+                let id = state.ids.next(received_at.timestamp_millis());
+                let payload = Payload {
+                    id,
+                    value: incoming.value,
+                    size: new_payload_size,
+                    kind: incoming.kind,
+                    channel: incoming.channel,
+                    received_at,
+                    request: incoming.request,
+                    pinned: false,
+                    annotations: incoming.annotations,
+                };
+                let mut records = vec![Record::Put { payload: Box::new(payload.clone()) }];
+                state.push(payload.clone());
+
+                let evicted = state.enforce_retention(received_at);
+                records.extend(evicted.iter().map(|payload| Record::Delete { id: payload.id.clone() }));
+                self.persist(records)?;
+                // Nobody listening is not an error
+                let _ = self.live.send(payload.clone());
+                Ok(Added {
+                    payload,
+                    evicted: evicted.into_iter().map(|payload| payload.id).collect(),
+                })
+            }
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in add_json: {poisoned}");
+                Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned").into())
+            }
+        }
+    }
+
+    /// Stores imported payloads at their original receive times, enforcing the retention policy.
+    /// Ids are derived from the receive time, with the sequence bumped past any id already in use.
+    pub fn import(&self, items: Vec<(Incoming, DateTime<Utc>)>) -> io::Result<ImportSummary> {
+        match self.data.lock() {
+            Ok(mut state) => {
+                let mut ids = state
+                    .payloads
+                    .iter()
+                    .map(|payload| payload.id.clone())
+                    .collect::<HashSet<_>>();
+                let imported = items.len();
+                let mut imported_ids = HashSet::new();
+
+                for (incoming, received_at) in items {
+                    let id = id::unused_id(received_at.timestamp_millis(), &ids);
+                    ids.insert(id.clone());
+                    imported_ids.insert(id.clone());
+
+                    state.insert_chronologically(Payload {
+                        id,
+                        size: estimate_payload_size(&incoming.value),
+                        value: incoming.value,
+                        kind: incoming.kind,
+                        channel: incoming.channel,
+                        received_at,
+                        request: incoming.request,
+                        pinned: false,
+                        annotations: incoming.annotations,
+                    });
+                }
+
+                let evicted = state.enforce_retention(Utc::now());
+
+                // The log replays in append order, so rewrite it to keep older imports in place
+                self.writer.send(Command::Compact)?;
+
+                for payload in state.payloads.iter().filter(|payload| imported_ids.contains(&payload.id)) {
+                    let _ = self.live.send(payload.clone());
+                }
+
+                Ok(ImportSummary {
+                    imported,
+                    evicted: evicted.len(),
+                })
+            }
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in import: {poisoned}");
+                Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned"))
+            }
+        }
+    }
+
+    /// Receives every payload stored from now on, including imported ones
+    pub fn subscribe(&self) -> broadcast::Receiver<Payload> {
+        self.live.subscribe()
+    }
+
+    /// Retrieves all stored payloads, newest first
+    pub fn get_all(&self) -> Vec<Payload> {
+        match self.data.lock() {
+            Ok(state) => {
+                state.payloads.iter().rev().cloned().collect()
+            }
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in get_all: {poisoned}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Lists every channel holding payloads with its payload count, sorted by name
+    pub fn channels(&self) -> Vec<(String, usize)> {
+        match self.data.lock() {
+            Ok(state) => state
+                .channel_counts
+                .iter()
+                .map(|(channel, count)| (channel.clone(), *count))
+                .collect(),
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in channels: {poisoned}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Deletes an item by ID
+    pub fn delete(&self, id: &str) -> io::Result<bool> {
+        let deletion_happened;
+        {
+            let mut state = match self.data.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => {
+                    eprintln!("ERROR: Storage mutex poisoned in delete: {poisoned}");
+                    return Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned"));
+                }
+            };
+
+            deletion_happened = match state.payloads.iter().position(|payload| payload.id == id) {
+                Some(index) => {
+                    let removed = state.remove(index);
+                    eprintln!("INFO: Deleted payload ({}), freed {} bytes. New total: {}", id, removed.size, state.total_bytes);
+                    self.persist(vec![Record::Delete { id: removed.id }])?;
+                    true
+                }
+                None => false,
+            };
+        } // Mutex guard dropped here
+
+        Ok(deletion_happened)
+    }
+
+    /// Pins or unpins a payload, returning whether it exists
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> io::Result<bool> {
+        let mut state = match self.data.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in set_pinned: {poisoned}");
+                return Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned"));
+            }
+        };
+
+        let Some(payload) = state.payloads.iter_mut().find(|payload| payload.id == id) else {
+            return Ok(false);
+        };
+        payload.pinned = pinned;
+        let record = Record::Put { payload: Box::new(payload.clone()) };
+        self.persist(vec![record])?;
+        Ok(true)
+    }
+
+    /// Deletes every payload in a channel, returning how many were removed
+    pub fn delete_channel(&self, channel: &str, keep_pinned: bool) -> io::Result<usize> {
+        let mut state = match self.data.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in delete_channel: {poisoned}");
+                return Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned"));
+            }
+        };
+
+        let removed = state.retain(|payload| payload.channel != channel || (keep_pinned && payload.pinned));
+        if !removed.is_empty() {
+            eprintln!("INFO: Cleared {} payloads from channel '{channel}'.", removed.len());
+            let tombstones = removed
+                .iter()
+                .map(|payload| Record::Delete { id: payload.id.clone() })
+                .collect::<Vec<_>>();
+            self.persist(tombstones)?;
+        }
+
+        Ok(removed.len())
+    }
+
+    /// Deletes all stored data, or everything except pinned payloads when `keep_pinned` is set
+    pub fn delete_all(&self, keep_pinned: bool) -> io::Result<()> {
+        match self.data.lock() {
+            Ok(mut state) => {
+                if keep_pinned && state.payloads.iter().any(|payload| payload.pinned) {
+                    let removed = state.retain(|payload| payload.pinned);
+                    eprintln!("INFO: Cleared {} unpinned payloads, keeping {} pinned.", removed.len(), state.payloads.len());
+                    let tombstones = removed
+                        .iter()
+                        .map(|payload| Record::Delete { id: payload.id.clone() })
+                        .collect::<Vec<_>>();
+                    self.persist(tombstones)
+                } else if !state.payloads.is_empty() {
+                     eprintln!("INFO: Clearing all {} payloads, freeing {} bytes.", state.payloads.len(), state.total_bytes);
+                     state.retain(|_| false);
+                     self.persist(vec![Record::Clear])
+                } else {
+                    eprintln!("INFO: delete_all called but no payloads to clear.");
+                    Ok(())
+                }
+            }
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in delete_all: {poisoned}");
+                Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned"))
+            }
+        }
+    }
+
+    /// Queues the records of a change already applied to the in-memory data for the log.
+    /// Called with `data` still locked, so records are written in the order they were applied.
+    fn persist(&self, records: Vec<Record>) -> io::Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.writer.send(Command::Append(records))
+    }
+
+    /// Resolves once every change made so far is on disk, without waiting for the next batch.
+    /// Fails when any write since the last flush failed.
+    pub fn flush(&self) -> impl Future<Output = io::Result<()>> + Send + 'static {
+        let (done, written) = oneshot::channel();
+        let sent = self.writer.send(Command::Flush(done));
+        async move {
+            sent?;
+            written
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "The payload writer has stopped"))?
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::temp_dir::TempDir;
+    use super::*;
+    use serde_json::json;
+
+    fn ids(payloads: &[Payload]) -> Vec<&str> {
+        payloads.iter().map(|payload| payload.id.as_str()).collect()
+    }
+
+    #[test]
+    fn legacy_data_file_moves_into_the_log_with_current_ids() {
+        let dir = TempDir::new();
+        fs::create_dir_all(dir.path()).unwrap();
+        fs::write(
+            dir.path().join("data.json"),
+            r#"[["1000", {"a": 1}], ["1000", {"b": 2}]]"#,
+        )
+        .unwrap();
+
+        let (_, payloads) = open_log(dir.path()).unwrap();
+        assert_eq!(ids(&payloads), ["0000000001000-0000", "0000000001000-0001"]);
+        assert!(!dir.path().join("data.json").exists());
+        assert!(dir.path().join("data.json.migrated").exists());
+    }
+
+    #[test]
+    fn migration_interrupted_before_removing_old_segments_keeps_one_copy() {
+        let dir = TempDir::new();
+        let segment = dir.path().join("log").join("segment-00000001.ndjson");
+        {
+            let (mut log, _) = Log::open(&dir.path().join("log")).unwrap();
+            let records = ["1000", "1001", "0000000001000-0000"].map(|id| Record::Put {
+                payload: Box::new(Payload::from_legacy(id.to_string(), json!({}), 2)),
+            });
+            log.append(&records).unwrap();
+        }
+        let old_segment = fs::read(&segment).unwrap();
+
+        let (log, migrated) = open_log(dir.path()).unwrap();
+        assert_eq!(
+            ids(&migrated),
+            [
+                "0000000001000-0001",
+                "0000000001001-0000",
+                "0000000001000-0000"
+            ]
+        );
+        drop(log);
+
+        // As if the process died between writing the compacted segment and removing the old one
+        fs::write(&segment, old_segment).unwrap();
+        let (_, payloads) = open_log(dir.path()).unwrap();
+        assert_eq!(payloads, migrated);
+    }
+}
@@ -0,0 +1,124 @@
+use super::log::{Log, Record};
+use super::StorageState;
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// How long the writer collects further changes before appending them as one batch
+const BATCH_DELAY: Duration = Duration::from_millis(100);
+
+pub(super) enum Command {
+    /// Records of a change already applied in memory
+    Append(Vec<Record>),
+    /// Rewrites the log from the in-memory payloads, for changes appends cannot express
+    Compact,
+    /// Writes everything sent so far right away, then reports whether any write failed since the last flush
+    Flush(oneshot::Sender<io::Result<()>>),
+}
+
+/// Changes gathered by the writer, written together
+#[derive(Default)]
+struct Batch {
+    records: Vec<Record>,
+    /// Once set, the log is rebuilt from memory, which already holds every record in the batch
+    compact: bool,
+    flushes: Vec<oneshot::Sender<io::Result<()>>>,
+}
+
+impl Batch {
+    fn add(&mut self, command: Command) {
+        match command {
+            Command::Append(records) if !self.compact => self.records.extend(records),
+            Command::Append(_) => {}
+            Command::Compact => {
+                self.records.clear();
+                self.compact = true;
+            }
+            Command::Flush(done) => self.flushes.push(done),
+        }
+    }
+
+    fn write(&self, log: &mut Log, state: &Mutex<StorageState>) -> io::Result<()> {
+        if !self.compact {
+            log.append(&self.records)?;
+            if !log.needs_compaction() {
+                return Ok(());
+            }
+        }
+
+        // Changes still queued behind this batch are already in memory; appending them
+        // again afterwards is harmless, as replaying a record twice has the same result
+        let payloads = match state.lock() {
+            Ok(state) => state.payloads.clone(),
+            Err(poisoned) => {
+                eprintln!("ERROR: Storage mutex poisoned in the payload writer: {poisoned}");
+                return Err(io::Error::new(io::ErrorKind::Other, "Mutex poisoned"));
+            }
+        };
+        log.compact(&payloads)
+    }
+}
+
+/// Handle to the background thread that owns the log, so changes are written
+/// without holding up whoever made them
+#[derive(Clone)]
+pub(super) struct Writer {
+    commands: Sender<Command>,
+}
+
+impl Writer {
+    /// Starts the writer thread; it stops once every handle is dropped and the queue is written
+    pub(super) fn spawn(log: Log, state: Arc<Mutex<StorageState>>) -> io::Result<Self> {
+        let (commands, queue) = mpsc::channel();
+        thread::Builder::new()
+            .name("payload-writer".to_string())
+            .spawn(move || run(log, &state, &queue))?;
+        Ok(Self { commands })
+    }
+
+    pub(super) fn send(&self, command: Command) -> io::Result<()> {
+        self.commands.send(command).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "The payload writer has stopped")
+        })
+    }
+}
+
+fn run(mut log: Log, state: &Mutex<StorageState>, queue: &Receiver<Command>) {
+    let mut failure: Option<io::Error> = None;
+
+    while let Ok(first) = queue.recv() {
+        let deadline = Instant::now() + BATCH_DELAY;
+        let mut batch = Batch::default();
+        let mut next = Some(first);
+
+        // Keep gathering until the delay is up, unless someone is waiting on the write
+        while let Some(command) = next {
+            batch.add(command);
+            if !batch.flushes.is_empty() {
+                break;
+            }
+            next = queue
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok();
+        }
+
+        if let Err(e) = batch.write(&mut log, state) {
+            eprintln!("ERROR: Failed to write payload log: {e}");
+            failure.get_or_insert(e);
+        }
+
+        if !batch.flushes.is_empty() {
+            let failure = failure.take();
+            for done in batch.flushes {
+                let result = match &failure {
+                    Some(e) => Err(io::Error::new(e.kind(), e.to_string())),
+                    None => Ok(()),
+                };
+                let _ = done.send(result);
+            }
+        }
+    }
+}